@@ -48,10 +48,12 @@ async fn main() -> Result<()> {
     tracing::subscriber::set_global_default(subscriber)?;
     let data_options = Options::new(
         vec!["E".to_string(), "M".to_string()],
-        NaiveDate::from_ymd(2020, 1, 1),
-        NaiveDate::from_ymd(2020, 12, 31),
+        NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(),
+        NaiveDate::from_ymd_opt(2020, 12, 31).unwrap(),
     )
     .set_resolution(Resolution::Minute);
-    let simulator = Simulator::new(Decimal::new(100000, 0), Strat, data_options);
+    let data_provider = PolygonProvider::from_env()?.file_cache("data");
+    let simulator =
+        Simulator::new(Decimal::new(100000, 0), Strat, data_options, data_provider).await?;
     simulator.run().await
 }
@@ -1,12 +1,22 @@
 use super::order::Order;
 use super::position::{Lot, Position};
+use chrono::NaiveDate;
 use rust_decimal::Decimal;
 use std::collections::HashMap;
 
+/// A dividend that the account is entitled to but that has not been paid yet.
+#[derive(Clone, Debug)]
+pub struct PendingDividend {
+    pub ticker: String,
+    pub pay_date: NaiveDate,
+    pub amount: Decimal,
+}
+
 pub struct Account {
     pub active_orders: Vec<Order>,
     pub inactive_orders: Vec<Order>,
     pub positions: HashMap<String, Position>,
+    pub pending_dividends: Vec<PendingDividend>,
     pub cash: Decimal,
 }
 
@@ -16,6 +26,7 @@ impl Account {
             active_orders: Vec::new(),
             inactive_orders: Vec::new(),
             positions: HashMap::new(),
+            pending_dividends: Vec::new(),
             cash,
         }
     }
@@ -28,6 +39,47 @@ impl Account {
             .or_insert_with(|| Position::new(ticker, lot));
     }
 
+    pub fn split(&mut self, ticker: &str, ratio: Decimal) {
+        if let Some(pos) = self.positions.get_mut(ticker) {
+            pos.split(ratio)
+        }
+    }
+
+    /// Records the dividend owed on the current position in `ticker`, which is negative for short
+    /// positions.
+    pub fn accrue_dividend(
+        &mut self,
+        ticker: &str,
+        pay_date: NaiveDate,
+        amount_per_share: Decimal,
+    ) {
+        let quantity = self
+            .positions
+            .get(ticker)
+            .map(|pos| pos.quantity())
+            .unwrap_or(Decimal::ZERO);
+        if !quantity.is_zero() {
+            self.pending_dividends.push(PendingDividend {
+                ticker: ticker.to_string(),
+                pay_date,
+                amount: quantity * amount_per_share,
+            })
+        }
+    }
+
+    /// Credits the cash for all dividends paid on or before `date` and returns them.
+    pub fn pay_dividends(&mut self, date: NaiveDate) -> Vec<PendingDividend> {
+        let (paid, pending) = self
+            .pending_dividends
+            .drain(..)
+            .partition(|dividend| dividend.pay_date <= date);
+        self.pending_dividends = pending;
+        for dividend in paid.iter() {
+            self.cash += dividend.amount;
+        }
+        paid
+    }
+
     pub fn market_value(&self, ticker: &str, price: Decimal) -> Decimal {
         self.positions
             .get(ticker)
@@ -58,7 +110,7 @@ mod test {
         account.add_lot(
             "AAPL".into(),
             Lot {
-                fill_time: Eastern.ymd(2021, 1, 1).and_hms(0, 0, 0),
+                fill_time: Eastern.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap(),
                 price: Decimal::new(2, 0),
                 quantity: Decimal::new(3, 0),
             },
@@ -69,4 +121,45 @@ mod test {
         let market_value = account.market_value("AAPL", Decimal::new(100, 0));
         assert_eq!(market_value, Decimal::new(300, 0));
     }
+
+    #[test]
+    fn it_handles_corporate_actions() {
+        let mut account = Account::new(Decimal::ONE_HUNDRED);
+        account.add_lot(
+            "AAPL".into(),
+            Lot {
+                fill_time: Eastern.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap(),
+                price: Decimal::new(20, 0),
+                quantity: Decimal::new(3, 0),
+            },
+        );
+        account.add_lot(
+            "MSFT".into(),
+            Lot {
+                fill_time: Eastern.with_ymd_and_hms(2021, 1, 1, 0, 0, 0).unwrap(),
+                price: Decimal::new(10, 0),
+                quantity: Decimal::new(-2, 0),
+            },
+        );
+        assert_eq!(account.cash, Decimal::new(60, 0));
+
+        account.split("AAPL", Decimal::new(4, 0));
+        let pos = account.positions.get("AAPL").unwrap();
+        assert_eq!(pos.quantity(), Decimal::new(12, 0));
+        assert_eq!(pos.average_price(), Some(Decimal::new(5, 0)));
+
+        let pay_date = NaiveDate::from_ymd_opt(2021, 1, 10).unwrap();
+        account.accrue_dividend("AAPL", pay_date, Decimal::new(5, 1));
+        account.accrue_dividend("MSFT", pay_date, Decimal::new(1, 0));
+        account.accrue_dividend("TSLA", pay_date, Decimal::new(1, 0));
+        assert_eq!(account.pending_dividends.len(), 2);
+
+        assert!(account
+            .pay_dividends(pay_date.pred_opt().unwrap())
+            .is_empty());
+        let paid = account.pay_dividends(pay_date);
+        assert_eq!(paid.len(), 2);
+        assert!(account.pending_dividends.is_empty());
+        assert_eq!(account.cash, Decimal::new(64, 0));
+    }
 }
@@ -1,17 +1,19 @@
 use crate::brokerage::account::Account;
+use crate::brokerage::fill::{execute, Execution, IntrabarPath};
 use crate::brokerage::handle::*;
-use crate::brokerage::order::{Order, OrderStatus};
+use crate::brokerage::order::{Order, OrderStatus, OrderType, TimeInForce};
 use crate::brokerage::position::Lot;
-use crate::finance::{
-    commission::{Commission, NoCommission},
-    slippage::{NoSlippage, Slippage},
+use crate::data::{corporate_actions::CorporateAction, Aggregate};
+use crate::finance::{commission::Commission, slippage::Slippage};
+use crate::markets::{
+    clock::MarketState,
+    handle::{Market, TickerSnapshot},
 };
-use crate::markets::handle::Market;
-use chrono::DateTime;
+use chrono::{DateTime, Duration};
 use chrono_tz::Tz;
-use futures::StreamExt;
-use rust_decimal::Decimal;
+use rust_decimal::prelude::*;
 use serde::Serialize;
+use std::sync::Arc;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::oneshot::Sender as OneshotSender;
 use tracing::{debug, trace};
@@ -22,24 +24,45 @@ pub enum Event {
     Commission {
         amount: Decimal,
     },
+    /// The cost of filling an order away from the market price
+    Slippage {
+        amount: Decimal,
+    },
     OrderUpdate {
         status: OrderStatus,
         time: DateTime<Tz>,
         order: Order,
     },
+    Split {
+        ticker: String,
+        ratio: Decimal,
+        time: DateTime<Tz>,
+    },
+    Dividend {
+        ticker: String,
+        amount: Decimal,
+        time: DateTime<Tz>,
+    },
 }
 
 pub struct BrokerageActor {
     requests: UnboundedReceiver<(OneshotSender<BrokerageResponse>, BrokerageRequest)>,
     account: Account,
     market: Market,
-    commission: Box<dyn Commission>,
-    _slippage: Box<dyn Slippage>,
+    commission: Arc<dyn Commission>,
+    slippage: Arc<dyn Slippage>,
+    intrabar_path: IntrabarPath,
     listeners: Vec<UnboundedSender<Event>>,
 }
 
 impl BrokerageActor {
-    pub fn spawn(cash: Decimal, market: Market) -> Brokerage {
+    pub fn spawn(
+        cash: Decimal,
+        market: Market,
+        commission: Arc<dyn Commission>,
+        slippage: Arc<dyn Slippage>,
+        intrabar_path: IntrabarPath,
+    ) -> Brokerage {
         let account = Account::new(cash);
 
         let (tx, rx) = unbounded_channel();
@@ -49,8 +72,9 @@ impl BrokerageActor {
             requests: rx,
             account,
             market,
-            commission: Box::new(NoCommission),
-            _slippage: Box::new(NoSlippage),
+            commission,
+            slippage,
+            intrabar_path,
             listeners: Vec::new(),
         };
         tokio::spawn(async move { actor.run_forever().await });
@@ -91,6 +115,10 @@ impl BrokerageActor {
                 self.expire_orders().await;
                 BrokerageResponse::Success
             }
+            BrokerageRequest::ProcessCorporateActions => {
+                self.process_corporate_actions().await;
+                BrokerageResponse::Success
+            }
             BrokerageRequest::Subscribe => {
                 let receiver = self.subscribe();
                 BrokerageResponse::EventListener(receiver)
@@ -100,17 +128,17 @@ impl BrokerageActor {
 
     #[tracing::instrument(skip(self))]
     async fn get_equity(&self) -> Decimal {
-        let tickers = self.account.positions.keys();
-        let equity = futures::stream::iter(tickers)
-            .fold(self.account.cash, |equity, ticker| async move {
-                let price = self.market.get_current_price(ticker).await;
-                let position_value = self
-                    .account
-                    .market_value(ticker, price.unwrap_or(Decimal::ZERO));
-                equity + position_value
+        let tickers: Vec<&String> = self.account.positions.keys().collect();
+        let prices = self.market.mark_prices(&tickers).await;
+        tickers
+            .into_iter()
+            .fold(self.account.cash, |equity, ticker| {
+                let price = prices.get(ticker).copied();
+                equity
+                    + self
+                        .account
+                        .market_value(ticker, price.unwrap_or(Decimal::ZERO))
             })
-            .await;
-        equity
     }
 
     #[tracing::instrument(skip(self))]
@@ -136,42 +164,167 @@ impl BrokerageActor {
 
     #[tracing::instrument(skip(self, order), fields(id = %order.id))]
     async fn send_order(&mut self, order: Order) {
-        if self.market.is_open().await {
-            let market = self.market.clone();
-            let (_, current_price) = futures::join!(
-                self.save_order(&order),
-                market.get_current_price(&order.ticker)
-            );
-            if let Some(price) = current_price {
-                if order.is_marketable(price) {
-                    self.fill_order(order, price).await;
-                }
-            }
-        } else {
-            trace!("Market closed");
+        let state = self.market.state().await;
+        let extended_hours = order.extended_hours
+            && matches!(state, MarketState::PreMarket | MarketState::AfterHours);
+        let is_open = matches!(
+            state,
+            MarketState::Opening | MarketState::Open | MarketState::Closing
+        );
+        let before_open = matches!(state, MarketState::PreOpen | MarketState::PreMarket);
+        // Auction orders can be placed ahead of their auction and wait for it
+        let (accepted, executes) = match order.time_in_force {
+            TimeInForce::OnOpen => (
+                before_open || state == MarketState::Opening,
+                state == MarketState::Opening,
+            ),
+            TimeInForce::OnClose => (before_open || is_open, state == MarketState::Closing),
+            TimeInForce::Day
+            | TimeInForce::GoodTillCancelled
+            | TimeInForce::ImmediateOrCancel
+            | TimeInForce::FillOrKill => (extended_hours || is_open, true),
+        };
+        if !accepted {
+            trace!(?state, "Market closed");
             self.reject_order(order).await;
+            return;
         }
+        if !executes {
+            self.save_order(&order).await;
+            return;
+        }
+        let market = self.market.clone();
+        let tickers = [&order.ticker];
+        let (_, snapshot, bars) = futures::join!(
+            self.save_order(&order),
+            market.snapshot(&tickers),
+            market.get_current_bars(&tickers)
+        );
+        let execution = order_execution(
+            &order,
+            snapshot.get(&order.ticker),
+            bars.get(&order.ticker),
+            extended_hours,
+            state == MarketState::Closing,
+            self.intrabar_path,
+        );
+        let volume = bars
+            .get(&order.ticker)
+            .map(|bar| bar.volume)
+            .unwrap_or_default();
+        self.execute_order(order, execution, volume).await;
     }
 
-    #[tracing::instrument(skip(self, order, price))]
-    async fn fill_order(&mut self, order: Order, price: Decimal) {
+    /// Triggers and fills `order` according to `execution` against a bar that traded `volume`.
+    /// Fill-or-kill orders only fill if all of their remaining shares can fill, and whatever
+    /// doesn't fill of immediate orders is cancelled.
+    async fn execute_order(&mut self, mut order: Order, execution: Execution, volume: Decimal) {
+        let id = order.id;
+        let time_in_force = order.time_in_force;
+        if let Some(trigger_price) = execution.trigger_price {
+            self.trigger_order(&mut order, trigger_price).await;
+        }
+        if let Some(price) = execution.fill_price {
+            let fills = time_in_force != TimeInForce::FillOrKill
+                || self.fillable_shares(&order, volume) == order.remaining_shares();
+            if fills {
+                self.fill_order(order, price, volume).await;
+            } else {
+                trace!("Order can't fill completely");
+            }
+        }
+        if time_in_force.is_immediate() {
+            if let Some(i) = self.account.active_orders.iter().position(|o| o.id == id) {
+                let order = self.account.active_orders.remove(i);
+                self.cancel_order(order).await;
+            }
+        }
+    }
+
+    /// The number of shares of `order` that can fill against a bar that traded `volume`, given the
+    /// volume cap of the slippage model.
+    fn fillable_shares(&self, order: &Order, volume: Decimal) -> Decimal {
+        let remaining = order.remaining_shares();
+        match self.slippage.max_volume_share() {
+            Some(max_volume_share) => {
+                let max_volume_share = Decimal::from_f64(max_volume_share).unwrap_or_default();
+                let max_shares = (volume * max_volume_share).floor();
+                remaining.abs().min(max_shares) * remaining.signum()
+            }
+            None => remaining,
+        }
+    }
+
+    #[tracing::instrument(skip(self, order), fields(id = %order.id))]
+    async fn trigger_order(&mut self, order: &mut Order, trigger_price: Decimal) {
+        debug!(%trigger_price, "Order triggered");
+        order.triggered = true;
+        if let Some(active) = self
+            .account
+            .active_orders
+            .iter_mut()
+            .find(|o| o.id == order.id)
+        {
+            active.triggered = true;
+        }
+        let event = Event::OrderUpdate {
+            status: OrderStatus::Triggered { trigger_price },
+            time: self.market.datetime().await,
+            order: order.clone(),
+        };
+        self.report_event(&event)
+    }
+
+    #[tracing::instrument(skip(self, order, market_price))]
+    async fn fill_order(&mut self, mut order: Order, market_price: Decimal, volume: Decimal) {
         let fill_time = self.market.datetime().await;
-        debug!(%fill_time, %price, "Order filled");
+        let shares = self.fillable_shares(&order, volume);
+        self.account.active_orders.retain(|o| o.id != order.id);
+        if shares.is_zero() {
+            trace!(%volume, "No volume left to fill against");
+            self.account.active_orders.push(order);
+            return;
+        }
+        let price = self.slippage.fill_price(market_price, shares, volume);
+        // Slippage can't push a fill past the limit
+        let price = match order.order_type {
+            OrderType::Limit(limit_price) | OrderType::StopLimit(_, limit_price) => {
+                if shares.is_sign_positive() {
+                    price.min(limit_price)
+                } else {
+                    price.max(limit_price)
+                }
+            }
+            OrderType::Market | OrderType::Stop(_) => price,
+        };
+        debug!(%fill_time, %price, %shares, "Order filled");
         let lot = Lot {
             fill_time,
             price,
-            quantity: order.shares,
+            quantity: shares,
         };
         let commission = self.commission.calculate(&lot);
+        let slippage = (price - market_price) * shares;
         self.account.add_lot(order.ticker.clone(), lot);
         self.account.cash -= commission;
-        self.account.inactive_orders.push(order.clone());
-        self.account.active_orders.retain(|o| o.id != order.id);
-        let event = Event::OrderUpdate {
-            status: OrderStatus::Filled {
+        order.fill(shares, price);
+        let average_fill_price = order.average_fill_price.unwrap_or(price);
+        let status = if order.remaining_shares().is_zero() {
+            self.account.inactive_orders.push(order.clone());
+            OrderStatus::Filled {
                 fill_time,
-                average_fill_price: price,
-            },
+                average_fill_price,
+            }
+        } else {
+            // The rest of the order fills against later bars
+            self.account.active_orders.push(order.clone());
+            OrderStatus::PartiallyFilled {
+                filled_shares: order.filled_shares,
+                average_fill_price,
+            }
+        };
+        let event = Event::OrderUpdate {
+            status,
             time: fill_time,
             order,
         };
@@ -180,6 +333,10 @@ impl BrokerageActor {
             let event = Event::Commission { amount: commission };
             self.report_event(&event);
         }
+        if !slippage.is_zero() {
+            let event = Event::Slippage { amount: slippage };
+            self.report_event(&event);
+        }
     }
 
     #[tracing::instrument(skip(self, order))]
@@ -240,33 +397,56 @@ impl BrokerageActor {
 
     #[tracing::instrument(skip(self))]
     async fn reconcile_active_orders(&mut self) {
-        // Manual version of drain_filter to be able to use the stable toolchain
-        // TODO: Change to use drain_filter once https://github.com/rust-lang/rust/issues/43244 is
-        // merged.
-        let mut i = 0;
-        let v = &mut self.account.active_orders;
-        let mut orders_to_send: Vec<Order> = Vec::new();
-        while i < v.len() {
-            let order = &v[i];
-            let price = self.market.get_current_price(&order.ticker).await;
-            if let Some(price) = price {
-                if order.is_marketable(price) {
-                    let val = v.remove(i);
-                    orders_to_send.push(val);
-                } else {
-                    i += 1
-                }
-            } else {
-                i += 1
+        let state = self.market.state().await;
+        let extended_hours = matches!(state, MarketState::PreMarket | MarketState::AfterHours);
+        // Auction orders only execute in their auction, and other orders not in the auctions
+        let eligible = |order: &Order| match order.time_in_force {
+            TimeInForce::OnOpen => state == MarketState::Opening,
+            TimeInForce::OnClose => state == MarketState::Closing,
+            TimeInForce::Day
+            | TimeInForce::GoodTillCancelled
+            | TimeInForce::ImmediateOrCancel
+            | TimeInForce::FillOrKill => {
+                !matches!(state, MarketState::Opening | MarketState::Closing)
             }
-        }
-        for order in orders_to_send {
-            let price = self
-                .market
-                .get_current_price(&order.ticker)
-                .await
-                .expect("Guaranteed to exist");
-            self.fill_order(order, price).await
+        };
+        let tickers: Vec<&String> = self
+            .account
+            .active_orders
+            .iter()
+            .filter(|order| eligible(order))
+            .map(|order| &order.ticker)
+            .collect();
+        let (snapshot, bars) = futures::join!(
+            self.market.snapshot(&tickers),
+            self.market.get_current_bars(&tickers)
+        );
+        let executions: Vec<(Order, Execution, Decimal)> = self
+            .account
+            .active_orders
+            .iter()
+            .filter(|order| eligible(order))
+            .map(|order| {
+                let execution = order_execution(
+                    order,
+                    snapshot.get(&order.ticker),
+                    bars.get(&order.ticker),
+                    extended_hours,
+                    state == MarketState::Closing,
+                    self.intrabar_path,
+                );
+                let volume = bars
+                    .get(&order.ticker)
+                    .map(|bar| bar.volume)
+                    .unwrap_or_default();
+                (order.clone(), execution, volume)
+            })
+            .filter(|(order, execution, _)| {
+                order.time_in_force.is_immediate() || *execution != Execution::default()
+            })
+            .collect();
+        for (order, execution, volume) in executions {
+            self.execute_order(order, execution, volume).await
         }
     }
 
@@ -283,13 +463,51 @@ impl BrokerageActor {
 
     #[tracing::instrument(skip(self))]
     async fn expire_orders(&mut self) {
-        loop {
-            let maybe_order = self.account.active_orders.pop();
-            match maybe_order {
-                Some(order) => self.expire_order(order).await,
-                None => return,
+        let (orders, good_till_cancelled): (Vec<Order>, Vec<Order>) = self
+            .account
+            .active_orders
+            .drain(..)
+            .partition(|order| order.time_in_force != TimeInForce::GoodTillCancelled);
+        self.account.active_orders = good_till_cancelled;
+        for order in orders.into_iter().rev() {
+            self.expire_order(order).await
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn process_corporate_actions(&mut self) {
+        let market = self.market.clone();
+        let (actions, time) = futures::join!(market.get_corporate_actions(), market.datetime());
+        let date = time.date_naive();
+        for action in actions {
+            match action {
+                CorporateAction::Split { ticker, ratio, .. } => {
+                    debug!(%ticker, %ratio, "Split");
+                    self.account.split(&ticker, ratio);
+                    let event = Event::Split {
+                        ticker,
+                        ratio,
+                        time,
+                    };
+                    self.report_event(&event)
+                }
+                CorporateAction::Dividend {
+                    ticker,
+                    pay_date,
+                    amount,
+                    ..
+                } => self.account.accrue_dividend(&ticker, pay_date, amount),
             }
         }
+        for dividend in self.account.pay_dividends(date) {
+            debug!(ticker = %dividend.ticker, amount = %dividend.amount, "Dividend paid");
+            let event = Event::Dividend {
+                ticker: dividend.ticker,
+                amount: dividend.amount,
+                time,
+            };
+            self.report_event(&event)
+        }
     }
 
     fn subscribe(&mut self) -> UnboundedReceiver<Event> {
@@ -298,3 +516,309 @@ impl BrokerageActor {
         rx
     }
 }
+
+/// How `order` executes against the current bar. During extended hours, only eligible orders
+/// execute, and only against bars traded at the current time rather than prices carried over from
+/// the regular session. At the close, orders only see the closing price, as the rest of the bar
+/// traded before they were placed.
+fn order_execution(
+    order: &Order,
+    snapshot: Option<&TickerSnapshot>,
+    bar: Option<&Aggregate>,
+    extended_hours: bool,
+    closing: bool,
+    path: IntrabarPath,
+) -> Execution {
+    let snapshot =
+        snapshot.filter(|s| !extended_hours || (order.extended_hours && s.age == Duration::zero()));
+    match (snapshot, bar) {
+        (Some(_), Some(bar)) if closing => {
+            let close = Aggregate {
+                open: bar.close,
+                high: bar.close,
+                low: bar.close,
+                ..bar.clone()
+            };
+            execute(order, &close, bar.close, path)
+        }
+        (Some(snapshot), Some(bar)) => execute(order, bar, snapshot.price, path),
+        _ => Execution::default(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::{
+        error::Error,
+        provider::DataProvider,
+        testing::{bar, flat_bar, opening},
+        MarketData,
+    };
+    use crate::finance::{
+        commission::NoCommission,
+        slippage::{FixedSlippage, NoSlippage, VolumeShareSlippage},
+    };
+    use crate::markets::{actor::MarketActor, data_manager::DataManager};
+    use crate::statistics::Statistics;
+    use crate::{MissingBarPolicy, Options, Resolution};
+    use async_trait::async_trait;
+    use chrono::{NaiveDate, TimeZone};
+    use chrono_tz::US::Eastern;
+
+    /// Returns the same bars for every ticker.
+    struct BarsProvider(Vec<Aggregate>);
+
+    #[async_trait]
+    impl DataProvider for BarsProvider {
+        async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+            Ok(meta
+                .tickers
+                .iter()
+                .map(|ticker| (ticker.clone(), self.0.clone()))
+                .collect())
+        }
+    }
+
+    /// Minute bars on 2021-01-05 from the open, trading at 100 with a volume of 10.
+    fn bars(n: i64) -> Vec<Aggregate> {
+        (0..n)
+            .map(|minute| Aggregate {
+                volume: Decimal::new(10, 0),
+                ..flat_bar(opening() + Duration::minutes(minute), 100)
+            })
+            .collect()
+    }
+
+    /// Minute-resolution options for AAPL on 2021-01-05.
+    fn options() -> Options {
+        let date = NaiveDate::from_ymd_opt(2021, 1, 5).unwrap();
+        Options::new(vec!["AAPL".to_string()], date, date).set_resolution(Resolution::Minute)
+    }
+
+    /// Sets up a market ticked to the open, and a brokerage trading in it.
+    async fn setup(
+        bars: Vec<Aggregate>,
+        options: Options,
+        slippage: impl Slippage + 'static,
+    ) -> (Market, Brokerage, UnboundedReceiver<Event>) {
+        let mut data_manager = DataManager::new(options.clone(), Box::new(BarsProvider(bars)));
+        data_manager.download_data().await.unwrap();
+        let market = MarketActor::spawn(options, data_manager);
+        let brokerage = BrokerageActor::spawn(
+            Decimal::new(100_000, 0),
+            market.clone(),
+            Arc::new(NoCommission),
+            Arc::new(slippage),
+            IntrabarPath::default(),
+        );
+        let events = brokerage.subscribe().await;
+        tick_until(&market, MarketState::Open).await;
+        (market, brokerage, events)
+    }
+
+    async fn tick_until(market: &Market, state: MarketState) {
+        while market.state().await != state {
+            market.tick().await;
+        }
+    }
+
+    /// The statuses of the order updates received so far.
+    fn statuses(events: &mut UnboundedReceiver<Event>) -> Vec<OrderStatus> {
+        let mut statuses = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let Event::OrderUpdate { status, .. } = event {
+                statuses.push(status)
+            }
+        }
+        statuses
+    }
+
+    #[tokio::test]
+    async fn it_fills_capped_orders_over_several_bars() {
+        let slippage = VolumeShareSlippage::new(0.0, Some(0.5));
+        let (market, brokerage, mut events) = setup(bars(2), options(), slippage).await;
+        brokerage
+            .send_order(Order::new("AAPL", Decimal::new(8, 0)))
+            .await;
+        match statuses(&mut events).as_slice() {
+            [OrderStatus::Submitted, OrderStatus::PartiallyFilled { filled_shares, .. }] => {
+                assert_eq!(*filled_shares, Decimal::new(5, 0))
+            }
+            other => panic!("Unexpected statuses: {:?}", other),
+        }
+
+        market.tick().await;
+        brokerage.reconcile_active_orders().await;
+        match statuses(&mut events).as_slice() {
+            [OrderStatus::Filled {
+                average_fill_price, ..
+            }] => assert_eq!(*average_fill_price, Decimal::new(100, 0)),
+            other => panic!("Unexpected statuses: {:?}", other),
+        }
+        let positions = brokerage.get_positions().await;
+        assert_eq!(positions[0].quantity(), Decimal::new(8, 0));
+    }
+
+    #[tokio::test]
+    async fn it_values_positions_at_the_last_price_without_a_bar() {
+        let options = options().set_missing_bar_policy(MissingBarPolicy::Strict);
+        let (market, brokerage, _events) = setup(bars(1), options, NoSlippage).await;
+        brokerage
+            .send_order(Order::new("AAPL", Decimal::new(10, 0)))
+            .await;
+        assert_eq!(
+            brokerage.get_positions().await[0].quantity(),
+            Decimal::new(10, 0)
+        );
+        assert_eq!(brokerage.get_equity().await, Decimal::new(100_000, 0));
+        // There is no bar at 09:31 to fill orders against, but the position is still worth 1000
+        market.tick().await;
+        assert!(market.snapshot(&["AAPL"]).await.is_empty());
+        assert_eq!(brokerage.get_equity().await, Decimal::new(100_000, 0));
+    }
+
+    #[tokio::test]
+    async fn it_only_fills_at_the_close_when_closing() {
+        let daily = vec![Aggregate {
+            volume: Decimal::new(10, 0),
+            ..bar(opening(), 100, 104, 95, 102)
+        }];
+        let options = options().set_resolution(Resolution::Day);
+        let (market, brokerage, mut events) = setup(daily, options, NoSlippage).await;
+        tick_until(&market, MarketState::Closing).await;
+        // The low traded before the order was placed
+        let buy = Order::new("AAPL", Decimal::ONE).limit_price(Decimal::new(98, 0));
+        brokerage.send_order(buy).await;
+        assert!(matches!(
+            statuses(&mut events).as_slice(),
+            [OrderStatus::Submitted]
+        ));
+        let sell = Order::new("AAPL", -Decimal::ONE).limit_price(Decimal::new(101, 0));
+        brokerage.send_order(sell).await;
+        match statuses(&mut events).as_slice() {
+            [OrderStatus::Submitted, OrderStatus::Filled {
+                average_fill_price, ..
+            }] => assert_eq!(*average_fill_price, Decimal::new(102, 0)),
+            other => panic!("Unexpected statuses: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_keeps_good_till_cancelled_orders_overnight() {
+        let (_, brokerage, mut events) = setup(bars(1), options(), NoSlippage).await;
+        let limit = Order::new("AAPL", Decimal::ONE).limit_price(Decimal::new(90, 0));
+        brokerage.send_order(limit.clone()).await;
+        brokerage
+            .send_order(limit.time_in_force(TimeInForce::GoodTillCancelled))
+            .await;
+        statuses(&mut events);
+        brokerage.expire_orders().await;
+        assert!(matches!(
+            statuses(&mut events).as_slice(),
+            [OrderStatus::Expired]
+        ));
+        brokerage.cancel_active_orders().await;
+        assert!(matches!(
+            statuses(&mut events).as_slice(),
+            [OrderStatus::Cancelled]
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_cancels_what_immediate_orders_cant_fill() {
+        let slippage = VolumeShareSlippage::new(0.0, Some(0.5));
+        let (_, brokerage, mut events) = setup(bars(1), options(), slippage).await;
+        let order = Order::new("AAPL", Decimal::new(8, 0));
+        brokerage
+            .send_order(order.clone().time_in_force(TimeInForce::ImmediateOrCancel))
+            .await;
+        match statuses(&mut events).as_slice() {
+            [OrderStatus::Submitted, OrderStatus::PartiallyFilled { filled_shares, .. }, OrderStatus::Cancelled] =>
+            {
+                assert_eq!(*filled_shares, Decimal::new(5, 0))
+            }
+            other => panic!("Unexpected statuses: {:?}", other),
+        }
+
+        // Only 5 of the 8 shares can fill against the bar's volume
+        brokerage
+            .send_order(order.time_in_force(TimeInForce::FillOrKill))
+            .await;
+        assert!(matches!(
+            statuses(&mut events).as_slice(),
+            [OrderStatus::Submitted, OrderStatus::Cancelled]
+        ));
+        let positions = brokerage.get_positions().await;
+        assert_eq!(positions[0].quantity(), Decimal::new(5, 0));
+    }
+
+    #[tokio::test]
+    async fn it_only_accepts_auction_orders_before_their_auction() {
+        let (market, brokerage, mut events) = setup(bars(1), options(), NoSlippage).await;
+        let order = Order::new("AAPL", Decimal::ONE);
+        brokerage
+            .send_order(order.clone().time_in_force(TimeInForce::OnOpen))
+            .await;
+        assert!(matches!(
+            statuses(&mut events).as_slice(),
+            [OrderStatus::Rejected]
+        ));
+        tick_until(&market, MarketState::Closed).await;
+        brokerage
+            .send_order(order.time_in_force(TimeInForce::OnClose))
+            .await;
+        assert!(matches!(
+            statuses(&mut events).as_slice(),
+            [OrderStatus::Rejected]
+        ));
+    }
+
+    #[tokio::test]
+    async fn it_fills_on_close_orders_at_the_last_regular_close() {
+        let closing = Eastern.with_ymd_and_hms(2021, 1, 5, 16, 0, 0).unwrap();
+        let mut bars = bars(389);
+        bars.push(flat_bar(closing - Duration::minutes(1), 105));
+        bars.push(flat_bar(closing, 110));
+        let options = options().set_extended_hours(true);
+        let (market, brokerage, mut events) = setup(bars, options, NoSlippage).await;
+        brokerage
+            .send_order(Order::new("AAPL", Decimal::ONE).time_in_force(TimeInForce::OnClose))
+            .await;
+        tick_until(&market, MarketState::Closing).await;
+        brokerage.reconcile_active_orders().await;
+        match statuses(&mut events).as_slice() {
+            [OrderStatus::Submitted, OrderStatus::Filled {
+                average_fill_price, ..
+            }] => assert_eq!(*average_fill_price, Decimal::new(105, 0)),
+            other => panic!("Unexpected statuses: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_reports_the_slippage_of_fills() {
+        let slippage = FixedSlippage::new(0.01);
+        let (_, brokerage, mut events) = setup(bars(1), options(), slippage).await;
+        brokerage
+            .send_order(Order::new("AAPL", Decimal::new(2, 0)))
+            .await;
+        brokerage
+            .send_order(Order::new("AAPL", -Decimal::ONE))
+            .await;
+        let mut statistics = Statistics::new();
+        while let Ok(event) = events.try_recv() {
+            statistics.handle_event(event);
+        }
+        // Buying 2 at 101 and selling 1 at 99 when the market is at 100
+        let amounts: Vec<Decimal> = statistics
+            .event_log
+            .iter()
+            .filter_map(|event| match event {
+                Event::Slippage { amount } => Some(*amount),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(amounts, vec![Decimal::new(2, 0), Decimal::ONE]);
+        assert_eq!(statistics.slippage_paid(), Decimal::new(3, 0));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::brokerage::order::{Order, OrderType, TimeInForce};
+use crate::data::Aggregate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::iter::once;
+
+/// The order in which prices are assumed to have been traded within a bar.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, Eq, Hash, PartialEq)]
+pub enum IntrabarPath {
+    /// Open, then high, then low, then close.
+    OpenHighLowClose,
+    /// Open, then low, then high, then close.
+    OpenLowHighClose,
+    /// Open, then whichever of the high and low is closer to the open, then close.
+    #[default]
+    NearestFirst,
+}
+
+impl IntrabarPath {
+    fn points(&self, bar: &Aggregate) -> [Decimal; 4] {
+        let high_first = match self {
+            Self::OpenHighLowClose => true,
+            Self::OpenLowHighClose => false,
+            Self::NearestFirst => bar.high - bar.open <= bar.open - bar.low,
+        };
+        if high_first {
+            [bar.open, bar.high, bar.low, bar.close]
+        } else {
+            [bar.open, bar.low, bar.high, bar.close]
+        }
+    }
+}
+
+/// What happens to an order during a bar.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct Execution {
+    /// The price the order's stop was triggered at, if it was triggered during the bar
+    pub trigger_price: Option<Decimal>,
+    /// The price the order fills at, if it fills during the bar
+    pub fill_price: Option<Decimal>,
+}
+
+/// Works out how `order` executes during `bar`. Market orders fill at `market_price`. Limit orders
+/// fill at their limit, or at the open if it gaps past the limit. Stops trigger at their stop, or
+/// at the open if it gaps past the stop, after which a stop order fills like a market order and a
+/// stop-limit order like a limit order. Auction orders only see the opening or closing price.
+pub(crate) fn execute(
+    order: &Order,
+    bar: &Aggregate,
+    market_price: Decimal,
+    path: IntrabarPath,
+) -> Execution {
+    let (points, market_price) = match order.time_in_force {
+        TimeInForce::OnOpen => (vec![bar.open], bar.open),
+        TimeInForce::OnClose => (vec![bar.close], bar.close),
+        TimeInForce::Day
+        | TimeInForce::GoodTillCancelled
+        | TimeInForce::ImmediateOrCancel
+        | TimeInForce::FillOrKill => (path.points(bar).to_vec(), market_price),
+    };
+    let buy = order.shares.is_sign_positive();
+    let limit = |path: &[Decimal], limit_price| touch(path, limit_price, buy).map(|(_, p)| p);
+    match order.order_type {
+        OrderType::Market => Execution {
+            trigger_price: None,
+            fill_price: Some(market_price),
+        },
+        OrderType::Limit(limit_price) => Execution {
+            trigger_price: None,
+            fill_price: limit(&points, limit_price),
+        },
+        OrderType::Stop(_) if order.triggered => Execution {
+            trigger_price: None,
+            fill_price: Some(market_price),
+        },
+        OrderType::Stop(stop_price) => {
+            let trigger_price = touch(&points, stop_price, !buy).map(|(_, price)| price);
+            Execution {
+                trigger_price,
+                fill_price: trigger_price,
+            }
+        }
+        OrderType::StopLimit(_, limit_price) if order.triggered => Execution {
+            trigger_price: None,
+            fill_price: limit(&points, limit_price),
+        },
+        OrderType::StopLimit(stop_price, limit_price) => {
+            match touch(&points, stop_price, !buy) {
+                Some((i, trigger_price)) => {
+                    // Once triggered, the order works as a limit order for the rest of the bar
+                    let rest: Vec<Decimal> = once(trigger_price)
+                        .chain(points[i..].iter().copied())
+                        .collect();
+                    Execution {
+                        trigger_price: Some(trigger_price),
+                        fill_price: limit(&rest, limit_price),
+                    }
+                }
+                None => Execution::default(),
+            }
+        }
+    }
+}
+
+/// Walks along `path` until the price reaches `level`, from above if `from_above` is set and from
+/// below otherwise. Returns the index of the first point at or past `level` and the price it was
+/// reached at, which is the first point itself if the path starts past `level`.
+fn touch(path: &[Decimal], level: Decimal, from_above: bool) -> Option<(usize, Decimal)> {
+    let reached = |price: &Decimal| {
+        if from_above {
+            *price <= level
+        } else {
+            *price >= level
+        }
+    };
+    match path.iter().position(reached)? {
+        0 => Some((0, path[0])),
+        i => Some((i, level)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::testing::{bar, opening};
+
+    #[test]
+    fn it_fills_limits_and_stops_within_the_bar() {
+        let path = IntrabarPath::default();
+        let market = Decimal::new(100, 0);
+        let bar = bar(opening(), 100, 104, 97, 102);
+        let buy = |order_type| Order {
+            order_type,
+            ..Order::new("AAPL", Decimal::ONE)
+        };
+        let sell = |order_type| Order {
+            order_type,
+            ..Order::new("AAPL", -Decimal::ONE)
+        };
+        let price = |order: Order| execute(&order, &bar, market, path).fill_price;
+
+        assert_eq!(price(buy(OrderType::Market)), Some(market));
+        // A buy limit between the low and the open fills at the limit
+        assert_eq!(
+            price(buy(OrderType::Limit(Decimal::new(98, 0)))),
+            Some(Decimal::new(98, 0))
+        );
+        assert_eq!(price(buy(OrderType::Limit(Decimal::new(96, 0)))), None);
+        assert_eq!(
+            price(sell(OrderType::Limit(Decimal::new(103, 0)))),
+            Some(Decimal::new(103, 0))
+        );
+        // Limits that the open has already passed fill at the open
+        assert_eq!(
+            price(buy(OrderType::Limit(Decimal::new(101, 0)))),
+            Some(market)
+        );
+        assert_eq!(
+            price(buy(OrderType::Stop(Decimal::new(103, 0)))),
+            Some(Decimal::new(103, 0))
+        );
+        // A stop that the bar gaps through fills at the open
+        assert_eq!(
+            price(sell(OrderType::Stop(Decimal::new(105, 0)))),
+            Some(market)
+        );
+        assert_eq!(price(sell(OrderType::Stop(Decimal::new(96, 0)))), None);
+    }
+
+    #[test]
+    fn it_follows_the_intrabar_path() {
+        let market = Decimal::new(100, 0);
+        let bar = bar(opening(), 100, 104, 97, 102);
+        // Triggered at 103 on the way to the high, then fills at 99 on the way to the low
+        let order = Order {
+            order_type: OrderType::StopLimit(Decimal::new(103, 0), Decimal::new(99, 0)),
+            ..Order::new("AAPL", Decimal::ONE)
+        };
+        assert_eq!(
+            execute(&order, &bar, market, IntrabarPath::OpenHighLowClose),
+            Execution {
+                trigger_price: Some(Decimal::new(103, 0)),
+                fill_price: Some(Decimal::new(99, 0)),
+            }
+        );
+        // The low comes before the trigger, and the price never returns to the limit
+        assert_eq!(
+            execute(&order, &bar, market, IntrabarPath::OpenLowHighClose),
+            Execution {
+                trigger_price: Some(Decimal::new(103, 0)),
+                fill_price: None,
+            }
+        );
+    }
+
+    #[test]
+    fn it_fills_auction_orders_at_the_auction_price() {
+        let market = Decimal::new(101, 0);
+        let bar = bar(opening(), 100, 104, 97, 102);
+        let order = Order::new("AAPL", Decimal::ONE);
+        let price =
+            |order: &Order| execute(order, &bar, market, IntrabarPath::default()).fill_price;
+        assert_eq!(price(&order), Some(market));
+        let on_open = order.clone().time_in_force(TimeInForce::OnOpen);
+        assert_eq!(price(&on_open), Some(Decimal::new(100, 0)));
+        let on_close = order.time_in_force(TimeInForce::OnClose);
+        assert_eq!(price(&on_close), Some(Decimal::new(102, 0)));
+        // The bar trades below the limit, but the close doesn't
+        let limit_on_close = on_close.limit_price(Decimal::new(99, 0));
+        assert_eq!(price(&limit_on_close), None);
+    }
+
+    #[test]
+    fn it_waits_for_stop_limits_to_trigger() {
+        let market = Decimal::new(100, 0);
+        let path = IntrabarPath::default();
+        // The price is below the limit, but the stop is never reached
+        let mut order = Order {
+            order_type: OrderType::StopLimit(Decimal::new(105, 0), Decimal::new(101, 0)),
+            ..Order::new("AAPL", Decimal::ONE)
+        };
+        assert_eq!(
+            execute(&order, &bar(opening(), 100, 104, 97, 102), market, path),
+            Execution::default()
+        );
+        // Once triggered, it rests as a limit order
+        order.triggered = true;
+        assert_eq!(
+            execute(&order, &bar(opening(), 100, 104, 97, 102), market, path).fill_price,
+            Some(market)
+        );
+        assert_eq!(
+            execute(&order, &bar(opening(), 103, 104, 102, 102), market, path).fill_price,
+            None
+        );
+
+        let mut order = Order {
+            order_type: OrderType::Stop(Decimal::new(105, 0)),
+            ..Order::new("AAPL", Decimal::ONE)
+        };
+        assert_eq!(
+            execute(&order, &bar(opening(), 100, 104, 97, 102), market, path),
+            Execution::default()
+        );
+        // Once triggered, it fills like a market order
+        order.triggered = true;
+        assert_eq!(
+            execute(&order, &bar(opening(), 100, 104, 97, 102), market, path).fill_price,
+            Some(market)
+        );
+    }
+}
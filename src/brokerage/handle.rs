@@ -14,6 +14,7 @@ pub(crate) enum BrokerageRequest {
     SendOrder(Order),
     ReconcileOrders,
     ExpireOrders,
+    ProcessCorporateActions,
     Subscribe,
 }
 
@@ -100,4 +101,10 @@ impl Brokerage {
     pub(crate) async fn expire_orders(&self) {
         self.send_request(BrokerageRequest::ExpireOrders).await;
     }
+
+    #[tracing::instrument(skip(self))]
+    pub(crate) async fn process_corporate_actions(&self) {
+        self.send_request(BrokerageRequest::ProcessCorporateActions)
+            .await;
+    }
 }
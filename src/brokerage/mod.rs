@@ -1,5 +1,6 @@
 pub mod account;
 pub mod actor;
+pub mod fill;
 pub mod handle;
 pub mod order;
 pub mod position;
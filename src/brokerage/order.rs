@@ -8,12 +8,19 @@ use uuid::Uuid;
 #[serde(rename_all = "snake_case")]
 pub enum OrderStatus {
     Submitted,
+    /// The stop price of a stop or stop-limit order was reached
+    Triggered {
+        trigger_price: Decimal,
+    },
     Cancelled,
     Filled {
         fill_time: DateTime<Tz>,
         average_fill_price: Decimal,
     },
-    PartiallyFilled,
+    PartiallyFilled {
+        filled_shares: Decimal,
+        average_fill_price: Decimal,
+    },
     Rejected,
     Expired,
 }
@@ -27,12 +34,51 @@ pub enum OrderType {
     StopLimit(Decimal, Decimal),
 }
 
+/// How long an order stays active before it's cancelled or expires.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeInForce {
+    /// Expires at the end of the trading day (DAY)
+    #[default]
+    Day,
+    /// Stays active until filled or cancelled (GTC)
+    GoodTillCancelled,
+    /// Fills as much as possible when placed and cancels the rest (IOC)
+    ImmediateOrCancel,
+    /// Fills completely when placed or is cancelled (FOK)
+    FillOrKill,
+    /// Fills in the opening auction at the opening price, cancelling whatever doesn't fill (OPG)
+    OnOpen,
+    /// Fills in the closing auction at the closing price, cancelling whatever doesn't fill (CLS)
+    OnClose,
+}
+
+impl TimeInForce {
+    /// Whether whatever doesn't fill the first time the order executes is cancelled.
+    pub fn is_immediate(&self) -> bool {
+        match self {
+            Self::Day | Self::GoodTillCancelled => false,
+            Self::ImmediateOrCancel | Self::FillOrKill | Self::OnOpen | Self::OnClose => true,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Order {
     pub id: Uuid,
     pub ticker: String,
     pub shares: Decimal,
     pub order_type: OrderType,
+    pub time_in_force: TimeInForce,
+    /// Whether the order may be placed and filled during pre-market and after-hours trading.
+    pub extended_hours: bool,
+    /// Whether the stop price has been reached, after which a stop order works as a market order
+    /// and a stop-limit order as a limit order
+    pub triggered: bool,
+    /// The number of shares filled so far
+    pub filled_shares: Decimal,
+    /// The average price of the shares filled so far
+    pub average_fill_price: Option<Decimal>,
 }
 
 impl Order {
@@ -42,6 +88,11 @@ impl Order {
             ticker: ticker.to_string(),
             shares: shares.round_dp(8),
             order_type: OrderType::Market,
+            time_in_force: TimeInForce::default(),
+            extended_hours: false,
+            triggered: false,
+            filled_shares: Decimal::ZERO,
+            average_fill_price: None,
         }
     }
 
@@ -67,30 +118,42 @@ impl Order {
         self
     }
 
-    pub(crate) fn is_marketable(&self, price: Decimal) -> bool {
-        match self.order_type {
-            OrderType::Market => true,
-            OrderType::Limit(limit_price) => {
-                if self.shares.is_sign_positive() {
-                    limit_price >= price
-                } else {
-                    limit_price <= price
-                }
-            }
-            OrderType::Stop(stop_price) => {
-                if self.shares.is_sign_positive() {
-                    stop_price <= price
-                } else {
-                    stop_price >= price
-                }
-            }
-            OrderType::StopLimit(stop_price, limit_price) => {
-                if self.shares.is_sign_positive() {
-                    stop_price <= price || limit_price >= price
-                } else {
-                    stop_price >= price || limit_price <= price
-                }
-            }
-        }
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
+    pub fn extended_hours(mut self, extended_hours: bool) -> Self {
+        self.extended_hours = extended_hours;
+        self
+    }
+
+    /// The number of shares still to be filled.
+    pub fn remaining_shares(&self) -> Decimal {
+        self.shares - self.filled_shares
+    }
+
+    /// Records a fill of `shares` at `price`.
+    pub(crate) fn fill(&mut self, shares: Decimal, price: Decimal) {
+        let filled_shares = self.filled_shares + shares;
+        let cost =
+            self.average_fill_price.unwrap_or_default() * self.filled_shares + price * shares;
+        self.average_fill_price = Some(cost / filled_shares);
+        self.filled_shares = filled_shares;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_tracks_partial_fills() {
+        let mut order = Order::new("AAPL", Decimal::new(-30, 0));
+        order.fill(Decimal::new(-10, 0), Decimal::new(100, 0));
+        assert_eq!(order.remaining_shares(), Decimal::new(-20, 0));
+        order.fill(Decimal::new(-20, 0), Decimal::new(103, 0));
+        assert!(order.remaining_shares().is_zero());
+        assert_eq!(order.average_fill_price, Some(Decimal::new(102, 0)));
     }
 }
@@ -70,6 +70,14 @@ impl Position {
         }
     }
 
+    /// Rescales every lot for a split where each share becomes `ratio` shares.
+    pub fn split(&mut self, ratio: Decimal) {
+        for lot in self.lots.iter_mut() {
+            lot.quantity *= ratio;
+            lot.price /= ratio;
+        }
+    }
+
     pub fn quantity(&self) -> Decimal {
         self.lots
             .iter()
@@ -1,26 +1,40 @@
-use super::{error::Error, provider::DataProvider, DataOptions, MarketData};
+use super::{
+    corporate_actions::CorporateActions,
+    error::Error,
+    provider::DataProvider,
+    report::{DownloadError, DownloadReport},
+    MarketData,
+};
+use crate::{Options, Resolution};
 use async_trait::async_trait;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::OpenOptions;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[async_trait]
 pub trait DataCache {
     type DataProvider;
 
     fn data_provider(&self) -> &Self::DataProvider;
-    fn is_cache_valid(&self, meta: &DataOptions) -> bool;
-    fn save_data(&self, meta: &DataOptions, data: &MarketData) -> Result<(), Error>;
-    async fn load_data(&self, meta: &DataOptions) -> Result<MarketData, Error>;
+    /// Returns the requests that need to be downloaded for the cache to cover `meta`.
+    fn missing_data(&self, meta: &Options) -> Vec<Options>;
+    /// Merges data downloaded for `requests` into the cache. Only the tickers and dates of
+    /// `requests` that returned data are recorded as covered.
+    fn save_data(&self, requests: &[Options], data: MarketData) -> Result<(), Error>;
+    async fn load_data(&self, meta: &Options) -> Result<MarketData, Error>;
 }
 
+/// Extension trait for wrapping any [`DataProvider`] in an on-disk cache.
 pub trait FileCache {
     fn file_cache<T: Into<PathBuf>>(self, dir: T) -> FileDataCache<Self>
     where
         Self: Sized;
 }
 
-impl<T: DataProvider + Send + Sync + 'static> FileCache for T {
+impl<T: DataProvider + 'static> FileCache for T {
     fn file_cache<T2: Into<PathBuf>>(self, dir: T2) -> FileDataCache<Self>
     where
         Self: Sized,
@@ -33,19 +47,106 @@ impl<T: DataProvider + Send + Sync + 'static> FileCache for T {
 impl<T> DataProvider for T
 where
     T: DataCache + Sync + Send,
-    T::DataProvider: DataProvider + Send + Sync,
+    T::DataProvider: DataProvider,
 {
-    async fn download_data(&self, meta: &DataOptions) -> Result<MarketData, Error> {
-        if self.is_cache_valid(meta) {
-            self.load_data(meta).await
-        } else {
-            let data = self.data_provider().download_data(meta).await?;
-            self.save_data(meta, &data)?;
+    async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+        let requests = self.missing_data(meta);
+        if requests.is_empty() {
+            return self.load_data(meta).await;
+        }
+        let mut data = MarketData::new();
+        let mut report = DownloadReport::default();
+        let mut downloaded = Vec::with_capacity(requests.len());
+        for request in requests.into_iter() {
+            let (received, failures) = match self.data_provider().download_data(&request).await {
+                Ok(received) => (received, DownloadReport::default()),
+                Err(Error::Download(e)) => (e.data, e.report),
+                Err(e) => return Err(e),
+            };
+            // Failed tickers aren't covered so that they're downloaded again next time
+            let failed = failures.tickers();
+            report.failures.extend(failures.failures);
+            for (ticker, aggregates) in received {
+                data.entry(ticker).or_default().extend(aggregates);
+            }
+            let mut request = request;
+            request.tickers.retain(|ticker| !failed.contains(ticker));
+            downloaded.push(request);
+        }
+        self.save_data(&downloaded, data)?;
+        let data = self.load_data(meta).await?;
+        if report.is_empty() {
             Ok(data)
+        } else {
+            Err(Error::Download(Box::new(DownloadError { report, data })))
+        }
+    }
+
+    async fn download_corporate_actions(&self, meta: &Options) -> Result<CorporateActions, Error> {
+        self.data_provider().download_corporate_actions(meta).await
+    }
+}
+
+/// Inclusive range of dates.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+struct DateRange {
+    start: NaiveDate,
+    end: NaiveDate,
+}
+
+/// The date ranges that have been downloaded for each ticker.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Coverage {
+    tickers: HashMap<String, Vec<DateRange>>,
+}
+
+impl Coverage {
+    fn gaps(&self, ticker: &str, start: NaiveDate, end: NaiveDate) -> Vec<DateRange> {
+        let mut gaps = Vec::new();
+        let mut cursor = start;
+        for range in self.tickers.get(ticker).into_iter().flatten() {
+            if range.start > end {
+                break;
+            }
+            if range.end < cursor {
+                continue;
+            }
+            if range.start > cursor {
+                gaps.push(DateRange {
+                    start: cursor,
+                    end: range.start.pred_opt().unwrap(),
+                });
+            }
+            cursor = range.end.succ_opt().unwrap();
+        }
+        if cursor <= end {
+            gaps.push(DateRange { start: cursor, end })
+        }
+        gaps
+    }
+
+    fn insert(&mut self, ticker: &str, range: DateRange) {
+        let ranges = self.tickers.entry(ticker.to_string()).or_default();
+        ranges.push(range);
+        ranges.sort_by_key(|r| r.start);
+        let mut merged: Vec<DateRange> = Vec::with_capacity(ranges.len());
+        for range in ranges.drain(..) {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end.succ_opt().unwrap() => {
+                    last.end = last.end.max(range.end)
+                }
+                _ => merged.push(range),
+            }
         }
+        *ranges = merged;
     }
 }
 
+/// Caches downloaded data as MessagePack in `dir`, with one subdirectory per resolution.
+///
+/// Only the tickers and dates not already covered by the cache are downloaded, and new data is
+/// merged into the existing files. Files are replaced atomically so that an interrupted write
+/// leaves the previous cache intact.
 pub struct FileDataCache<D> {
     dir: PathBuf,
     data_provider: D,
@@ -61,82 +162,330 @@ impl<D> FileDataCache<D> {
             dir: dir.into(),
         }
     }
+
+    fn path(&self, resolution: Resolution, file: &str) -> PathBuf {
+        let mut path = self.dir.clone();
+        path.push(match resolution {
+            Resolution::Second => "second".to_string(),
+            Resolution::Minute => "minute".to_string(),
+            Resolution::Hour => "hour".to_string(),
+            Resolution::Day => "day".to_string(),
+            Resolution::Custom(step) => format!("{}ms", step.num_milliseconds()),
+        });
+        path.push(file);
+        path
+    }
+
+    fn read_coverage(&self, resolution: Resolution) -> Coverage {
+        std::fs::read(self.path(resolution, "meta.data"))
+            .ok()
+            .and_then(|bytes| rmp_serde::from_slice(&bytes).ok())
+            .unwrap_or_default()
+    }
+
+    fn read_prices(&self, resolution: Resolution) -> Result<MarketData, Error> {
+        let path = self.path(resolution, "prices.data");
+        if !path.exists() {
+            return Ok(MarketData::new());
+        }
+        let bytes = std::fs::read(path)?;
+        Ok(rmp_serde::from_slice(&bytes)?)
+    }
+}
+
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), Error> {
+    let tmp = path.with_extension("tmp");
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&tmp)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    std::fs::rename(tmp, path)?;
+    Ok(())
 }
 
 #[async_trait]
-impl<T: DataProvider + Send + Sync> DataCache for FileDataCache<T> {
+impl<T: DataProvider> DataCache for FileDataCache<T> {
     type DataProvider = T;
     fn data_provider(&self) -> &T {
         &self.data_provider
     }
 
-    fn is_cache_valid(&self, meta: &DataOptions) -> bool {
-        let mut path = self.dir.clone();
-        path.push("meta.data");
-        if path.exists() {
-            let bytes = std::fs::read(path);
-            if let Ok(bytes) = bytes {
-                let cached_meta = rmp_serde::from_slice::<DataOptions>(&bytes);
-                if let Ok(cached_meta) = cached_meta {
-                    let ticker_check = meta
-                        .tickers
-                        .iter()
-                        .all(|ticker| cached_meta.tickers.contains(ticker));
-                    let date_check = cached_meta.start <= meta.start && cached_meta.end >= meta.end;
-                    if ticker_check && date_check {
-                        return true;
-                    }
+    fn missing_data(&self, meta: &Options) -> Vec<Options> {
+        let coverage = self.read_coverage(meta.resolution);
+        // Group tickers with identical gaps so that they can be downloaded together
+        let mut gaps: Vec<(DateRange, Vec<String>)> = Vec::new();
+        for ticker in meta.tickers.iter() {
+            for gap in coverage.gaps(ticker, meta.start, meta.end) {
+                match gaps.iter_mut().find(|(range, _)| *range == gap) {
+                    Some((_, tickers)) => tickers.push(ticker.clone()),
+                    None => gaps.push((gap, vec![ticker.clone()])),
                 }
             }
         }
-        false
+        gaps.into_iter()
+            .map(|(range, tickers)| Options {
+                tickers,
+                start: range.start,
+                end: range.end,
+                ..meta.clone()
+            })
+            .collect()
     }
 
-    fn save_data(&self, meta: &DataOptions, data: &MarketData) -> Result<(), Error> {
-        let mut path = self.dir.clone();
-        std::fs::create_dir_all(path.clone())?;
-        path.push("meta.data");
-        let mut file = OpenOptions::new().create(true).write(true).open(path)?;
-        let bytes = rmp_serde::to_vec(meta)?;
-        file.write_all(&bytes)?;
-        let mut path = self.dir.clone();
-        path.push("prices.data");
-        let mut file = OpenOptions::new().create(true).write(true).open(path)?;
-        let bytes = rmp_serde::to_vec(&data)?;
-        file.write_all(&bytes)?;
+    fn save_data(&self, requests: &[Options], data: MarketData) -> Result<(), Error> {
+        let resolution = match requests.first() {
+            Some(request) => request.resolution,
+            None => return Ok(()),
+        };
+        std::fs::create_dir_all(self.path(resolution, ""))?;
+        let mut coverage = self.read_coverage(resolution);
+        for request in requests {
+            for ticker in request.tickers.iter() {
+                let returned_data = data.get(ticker).is_some_and(|aggregates| {
+                    aggregates.iter().any(|agg| {
+                        let date = agg.datetime.date_naive();
+                        date >= request.start && date <= request.end
+                    })
+                });
+                if returned_data {
+                    coverage.insert(
+                        ticker,
+                        DateRange {
+                            start: request.start,
+                            end: request.end,
+                        },
+                    );
+                }
+            }
+        }
+        let mut prices = self.read_prices(resolution)?;
+        for (ticker, aggregates) in data {
+            let cached = prices.entry(ticker).or_default();
+            cached.extend(aggregates);
+            cached.sort_by_key(|agg| agg.datetime);
+            cached.dedup_by_key(|agg| agg.datetime);
+        }
+        // Prices are written first so that the coverage never claims data that isn't stored
+        write_atomic(
+            &self.path(resolution, "prices.data"),
+            &rmp_serde::to_vec(&prices)?,
+        )?;
+        write_atomic(
+            &self.path(resolution, "meta.data"),
+            &rmp_serde::to_vec(&coverage)?,
+        )?;
         Ok(())
     }
 
-    async fn load_data(&self, meta: &DataOptions) -> Result<MarketData, Error> {
-        let mut path = self.dir.clone();
-        path.push("prices.data");
-        let bytes = std::fs::read(path)?;
-        let mut data: MarketData = rmp_serde::from_slice(&bytes)?;
-        let t_idx = data
-            .timestamps
-            .iter()
-            .map(|t| t.naive_utc().date() >= meta.start && t.naive_utc().date() <= meta.end);
-        let (new_data, new_timestamps) = data
-            .data
-            .iter()
-            .zip(data.timestamps.clone())
-            .zip(t_idx)
-            .filter_map(|((r, t), i)| if i { Some((r.clone(), t)) } else { None })
-            .unzip();
-        // TODO: Filter tickers
-        //let ticker_idx = data
-        //    .tickers
-        //    .iter()
-        //    .map(|ticker| meta.tickers.contains(ticker));
-        //let (new_data, new_tickers) = new_data
-        //    .data
-        //    .iter()
-        //    .zip(data.timestamps.clone())
-        //    .zip(t_idx)
-        //    .filter_map(|((r, t), i)| if i { Some((r.clone(), t)) } else { None })
-        //    .unzip();
-        data.data = new_data;
-        data.timestamps = new_timestamps;
+    async fn load_data(&self, meta: &Options) -> Result<MarketData, Error> {
+        let mut data = self.read_prices(meta.resolution)?;
+        data.retain(|ticker, _| meta.tickers.contains(ticker));
+        for aggregates in data.values_mut() {
+            aggregates.retain(|agg| {
+                let date = agg.datetime.date_naive();
+                date >= meta.start && date <= meta.end
+            });
+        }
         Ok(data)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::{
+        report::{DownloadErrorKind, DownloadFailure},
+        testing::flat_bar,
+        Aggregate,
+    };
+    use chrono::{Datelike, TimeZone};
+    use chrono_tz::US::Eastern;
+    use rust_decimal::Decimal;
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    struct RecordingProvider {
+        requests: Mutex<Vec<(Vec<String>, NaiveDate, NaiveDate)>>,
+    }
+
+    #[async_trait]
+    impl DataProvider for RecordingProvider {
+        async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((meta.tickers.clone(), meta.start, meta.end));
+            let mut data = MarketData::new();
+            let mut report = DownloadReport::default();
+            for ticker in meta.tickers.iter() {
+                match ticker.as_str() {
+                    "EMPTY" => continue,
+                    "FAIL" => {
+                        report.failures.push(DownloadFailure {
+                            ticker: ticker.clone(),
+                            page: None,
+                            kind: DownloadErrorKind::Request,
+                            message: "Timed out".into(),
+                        });
+                        continue;
+                    }
+                    _ => (),
+                }
+                let mut date = meta.start;
+                while date <= meta.end {
+                    let datetime = Eastern
+                        .from_local_datetime(&date.and_hms_opt(9, 30, 0).unwrap())
+                        .unwrap();
+                    data.entry(ticker.clone()).or_default().push(Aggregate {
+                        volume: Decimal::from(date.day()),
+                        ..flat_bar(datetime, 1)
+                    });
+                    date = date.succ_opt().unwrap();
+                }
+            }
+            if report.is_empty() {
+                Ok(data)
+            } else {
+                Err(Error::Download(Box::new(DownloadError { report, data })))
+            }
+        }
+    }
+
+    fn requests(
+        cache: &FileDataCache<RecordingProvider>,
+    ) -> Vec<(Vec<String>, NaiveDate, NaiveDate)> {
+        cache
+            .data_provider()
+            .requests
+            .lock()
+            .unwrap()
+            .drain(..)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn it_reuses_cached_data() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("backtester-{}", Uuid::new_v4()));
+        let cache = RecordingProvider {
+            requests: Mutex::new(Vec::new()),
+        }
+        .file_cache(&dir);
+
+        let options = Options::new(
+            vec!["AAPL".into(), "MSFT".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 31).unwrap(),
+        );
+        let data = cache.download_data(&options).await.unwrap();
+        assert_eq!(data["AAPL"].len(), 31);
+        assert_eq!(requests(&cache).len(), 1);
+
+        let options = Options::new(
+            vec!["AAPL".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 10).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 20).unwrap(),
+        );
+        let data = cache.download_data(&options).await.unwrap();
+        assert!(requests(&cache).is_empty());
+        assert_eq!(data.len(), 1);
+        assert_eq!(data["AAPL"].len(), 11);
+        assert_eq!(data["AAPL"][0].volume, Decimal::new(10, 0));
+
+        let options = options.set_resolution(Resolution::Minute);
+        cache.download_data(&options).await.unwrap();
+        assert_eq!(requests(&cache).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_only_downloads_missing_data() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("backtester-{}", Uuid::new_v4()));
+        let cache = RecordingProvider {
+            requests: Mutex::new(Vec::new()),
+        }
+        .file_cache(&dir);
+
+        let options = Options::new(
+            vec!["AAPL".into(), "MSFT".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 31).unwrap(),
+        );
+        cache.download_data(&options).await.unwrap();
+        requests(&cache);
+
+        let options = Options::new(
+            vec!["AAPL".into(), "MSFT".into(), "TSLA".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 2, 28).unwrap(),
+        );
+        let data = cache.download_data(&options).await.unwrap();
+        assert_eq!(
+            requests(&cache),
+            vec![
+                (
+                    vec!["AAPL".to_string(), "MSFT".to_string()],
+                    NaiveDate::from_ymd_opt(2021, 2, 1).unwrap(),
+                    NaiveDate::from_ymd_opt(2021, 2, 28).unwrap()
+                ),
+                (
+                    vec!["TSLA".to_string()],
+                    NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+                    NaiveDate::from_ymd_opt(2021, 2, 28).unwrap()
+                ),
+            ]
+        );
+        assert_eq!(data["AAPL"].len(), 59);
+        assert_eq!(data["TSLA"].len(), 59);
+
+        cache.download_data(&options).await.unwrap();
+        assert!(requests(&cache).is_empty());
+    }
+
+    #[tokio::test]
+    async fn it_keeps_partial_downloads() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("backtester-{}", Uuid::new_v4()));
+        let cache = RecordingProvider {
+            requests: Mutex::new(Vec::new()),
+        }
+        .file_cache(&dir);
+
+        let options = Options::new(
+            vec!["AAPL".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 31).unwrap(),
+        );
+        cache.download_data(&options).await.unwrap();
+        requests(&cache);
+
+        let options = Options::new(
+            vec!["AAPL".into(), "FAIL".into(), "EMPTY".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 2, 28).unwrap(),
+        );
+        match cache.download_data(&options).await {
+            Err(Error::Download(e)) => {
+                assert_eq!(e.report.tickers(), vec!["FAIL".to_string()]);
+                // Data from the earlier requests is kept along with the cached data
+                assert_eq!(e.data["AAPL"].len(), 59);
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert_eq!(requests(&cache).len(), 2);
+
+        // Neither the failed nor the empty ticker is considered covered
+        let _ = cache.download_data(&options).await;
+        assert_eq!(
+            requests(&cache),
+            vec![(
+                vec!["FAIL".to_string(), "EMPTY".to_string()],
+                NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2021, 2, 28).unwrap()
+            )]
+        );
+    }
+}
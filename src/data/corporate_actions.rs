@@ -0,0 +1,281 @@
+use super::{error::Error, provider::DataProvider, Aggregate, MarketData};
+use crate::Options;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate};
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CorporateAction {
+    /// A split where each share becomes `ratio` shares, e.g. 4 for a 4-for-1 split or 0.1 for a
+    /// 1-for-10 reverse split.
+    Split {
+        ticker: String,
+        ex_date: NaiveDate,
+        ratio: Decimal,
+    },
+    /// A cash dividend of `amount` per share.
+    Dividend {
+        ticker: String,
+        ex_date: NaiveDate,
+        pay_date: NaiveDate,
+        amount: Decimal,
+    },
+}
+
+impl CorporateAction {
+    pub fn ticker(&self) -> &str {
+        match self {
+            Self::Split { ticker, .. } | Self::Dividend { ticker, .. } => ticker,
+        }
+    }
+
+    pub fn ex_date(&self) -> NaiveDate {
+        match self {
+            Self::Split { ex_date, .. } | Self::Dividend { ex_date, .. } => *ex_date,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CorporateActions {
+    pub actions: Vec<CorporateAction>,
+}
+
+impl CorporateActions {
+    pub fn new(actions: Vec<CorporateAction>) -> Self {
+        Self { actions }
+    }
+
+    /// Reads corporate actions from a CSV file with the columns
+    /// `ticker,kind,ex_date,pay_date,value`, where `kind` is `split` or `dividend`, dates are
+    /// formatted as `%Y-%m-%d` and `value` is the split ratio or the dividend per share.
+    pub fn from_csv<T: AsRef<Path>>(path: T) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut reader = ::csv::Reader::from_path(path).map_err(|e| Error::Parse {
+            path: path.to_path_buf(),
+            line: 0,
+            message: e.to_string(),
+        })?;
+        let mut actions = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| Error::Parse {
+                path: path.to_path_buf(),
+                line: e.position().map(|p| p.line()).unwrap_or_default(),
+                message: e.to_string(),
+            })?;
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            let parse_error = |message: String| Error::Parse {
+                path: path.to_path_buf(),
+                line,
+                message,
+            };
+            let field = |idx: usize| {
+                record
+                    .get(idx)
+                    .map(str::trim)
+                    .ok_or_else(|| parse_error(format!("Missing field {}", idx)))
+            };
+            let date = |idx: usize| {
+                let value = field(idx)?;
+                NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                    .map_err(|e| parse_error(format!("Invalid date {:?}: {}", value, e)))
+            };
+            let ticker = field(0)?.to_string();
+            let value = Decimal::from_str(field(4)?)
+                .map_err(|e| parse_error(format!("Invalid value: {}", e)))?;
+            let action = match field(1)? {
+                "split" => CorporateAction::Split {
+                    ticker,
+                    ex_date: date(2)?,
+                    ratio: value,
+                },
+                "dividend" => CorporateAction::Dividend {
+                    ticker,
+                    ex_date: date(2)?,
+                    pay_date: date(3)?,
+                    amount: value,
+                },
+                kind => return Err(parse_error(format!("Unknown corporate action {}", kind))),
+            };
+            actions.push(action);
+        }
+        Ok(Self::new(actions))
+    }
+
+    /// The actions for `meta.tickers` with an ex-date between `meta.start` and `meta.end`.
+    pub fn filter(&self, meta: &Options) -> Self {
+        Self::new(
+            self.actions
+                .iter()
+                .filter(|action| {
+                    meta.tickers.iter().any(|t| t == action.ticker())
+                        && action.ex_date() >= meta.start
+                        && action.ex_date() <= meta.end
+                })
+                .cloned()
+                .collect(),
+        )
+    }
+
+    pub fn for_ticker<'a>(&'a self, ticker: &'a str) -> impl Iterator<Item = &'a CorporateAction> {
+        self.actions.iter().filter(move |a| a.ticker() == ticker)
+    }
+}
+
+/// Back-adjusts the prices and volumes of `series` for `actions`, so that the most recent bars are
+/// unchanged and earlier bars are comparable to them.
+///
+/// Splits scale prices by `1 / ratio` and volumes by `ratio`. Dividends scale prices by
+/// `1 - amount / close`, using the close of the last bar before the ex-date.
+pub(crate) fn back_adjust<'a>(
+    series: &mut BTreeMap<DateTime<Tz>, Aggregate>,
+    actions: impl Iterator<Item = &'a CorporateAction>,
+) {
+    // (ex-date, price factor, volume factor), computed from the unadjusted series
+    let mut factors: Vec<(NaiveDate, Decimal, Decimal)> = actions
+        .filter_map(|action| match action {
+            CorporateAction::Split { ex_date, ratio, .. } if !ratio.is_zero() => {
+                Some((*ex_date, Decimal::ONE / ratio, *ratio))
+            }
+            CorporateAction::Dividend {
+                ex_date, amount, ..
+            } => {
+                let previous_close = series
+                    .values()
+                    .take_while(|agg| agg.datetime.date_naive() < *ex_date)
+                    .last()?
+                    .close;
+                if previous_close.is_zero() {
+                    return None;
+                }
+                Some((
+                    *ex_date,
+                    Decimal::ONE - amount / previous_close,
+                    Decimal::ONE,
+                ))
+            }
+            _ => None,
+        })
+        .collect();
+    factors.sort_by_key(|(ex_date, ..)| Reverse(*ex_date));
+
+    let mut factors = factors.into_iter().peekable();
+    let mut price_factor = Decimal::ONE;
+    let mut volume_factor = Decimal::ONE;
+    for agg in series.values_mut().rev() {
+        let date = agg.datetime.date_naive();
+        while let Some((_, price, volume)) = factors.next_if(|(ex_date, ..)| date < *ex_date) {
+            price_factor *= price;
+            volume_factor *= volume;
+        }
+        agg.open *= price_factor;
+        agg.high *= price_factor;
+        agg.low *= price_factor;
+        agg.close *= price_factor;
+        agg.volume *= volume_factor;
+    }
+}
+
+/// Extension trait for attaching corporate actions to any [`DataProvider`].
+pub trait CorporateActionsExt {
+    fn with_corporate_actions(self, actions: CorporateActions) -> WithCorporateActions<Self>
+    where
+        Self: Sized;
+}
+
+impl<T: DataProvider + 'static> CorporateActionsExt for T {
+    fn with_corporate_actions(self, actions: CorporateActions) -> WithCorporateActions<Self> {
+        WithCorporateActions {
+            data_provider: self,
+            actions,
+        }
+    }
+}
+
+/// A [`DataProvider`] that supplements another provider with a fixed set of corporate actions.
+pub struct WithCorporateActions<D> {
+    data_provider: D,
+    actions: CorporateActions,
+}
+
+#[async_trait]
+impl<D: DataProvider> DataProvider for WithCorporateActions<D> {
+    async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+        self.data_provider.download_data(meta).await
+    }
+
+    async fn download_corporate_actions(&self, meta: &Options) -> Result<CorporateActions, Error> {
+        let mut actions = self.data_provider.download_corporate_actions(meta).await?;
+        actions.actions.extend(self.actions.filter(meta).actions);
+        Ok(actions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::testing::flat_bar;
+    use chrono::TimeZone;
+    use chrono_tz::US::Eastern;
+
+    fn daily_bar(day: u32, close: i64, volume: i64) -> (DateTime<Tz>, Aggregate) {
+        let agg = Aggregate {
+            volume: Decimal::new(volume, 0),
+            ..flat_bar(
+                Eastern.with_ymd_and_hms(2021, 1, day, 9, 30, 0).unwrap(),
+                close,
+            )
+        };
+        (agg.datetime, agg)
+    }
+
+    #[test]
+    fn it_back_adjusts_for_splits_and_dividends() {
+        let mut series: BTreeMap<_, _> = vec![
+            daily_bar(4, 400, 10),
+            daily_bar(5, 100, 40),
+            daily_bar(6, 99, 40),
+            daily_bar(7, 100, 40),
+        ]
+        .into_iter()
+        .collect();
+        let actions = CorporateActions::new(vec![
+            CorporateAction::Split {
+                ticker: "AAPL".into(),
+                ex_date: NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+                ratio: Decimal::new(4, 0),
+            },
+            CorporateAction::Dividend {
+                ticker: "AAPL".into(),
+                ex_date: NaiveDate::from_ymd_opt(2021, 1, 7).unwrap(),
+                pay_date: NaiveDate::from_ymd_opt(2021, 1, 20).unwrap(),
+                amount: Decimal::new(99, 2),
+            },
+            CorporateAction::Split {
+                ticker: "MSFT".into(),
+                ex_date: NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+                ratio: Decimal::new(2, 0),
+            },
+        ]);
+        back_adjust(&mut series, actions.for_ticker("AAPL"));
+        let closes: Vec<Decimal> = series.values().map(|agg| agg.close).collect();
+        let volumes: Vec<Decimal> = series.values().map(|agg| agg.volume).collect();
+        assert_eq!(
+            closes,
+            vec![
+                Decimal::new(99, 0),
+                Decimal::new(99, 0),
+                Decimal::new(9801, 2),
+                Decimal::new(100, 0)
+            ]
+        );
+        assert_eq!(volumes, vec![Decimal::new(40, 0); 4]);
+    }
+}
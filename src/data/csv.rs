@@ -0,0 +1,366 @@
+use super::{
+    corporate_actions::CorporateActions,
+    error::Error,
+    provider::DataProvider,
+    report::{DownloadError, DownloadFailure, DownloadReport},
+    Aggregate, MarketData,
+};
+use crate::Options;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::{Tz, US::Eastern};
+use rust_decimal::Decimal;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Names of the columns holding each field of an [`Aggregate`].
+#[derive(Clone, Debug)]
+pub struct CsvColumns {
+    pub datetime: String,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    pub volume: String,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            datetime: "datetime".to_string(),
+            open: "open".to_string(),
+            high: "high".to_string(),
+            low: "low".to_string(),
+            close: "close".to_string(),
+            volume: "volume".to_string(),
+        }
+    }
+}
+
+/// How the datetime column is encoded.
+#[derive(Clone, Debug)]
+pub enum TimestampFormat {
+    /// Seconds since the Unix epoch.
+    UnixSeconds,
+    /// Milliseconds since the Unix epoch.
+    UnixMillis,
+    /// RFC 3339 datetime with an explicit offset.
+    Rfc3339,
+    /// `chrono` format string for a datetime without an offset, in the provider's timezone.
+    DateTime(String),
+    /// `chrono` format string for a date, stamped at midnight in the provider's timezone.
+    Date(String),
+}
+
+/// Reads aggregates from a directory containing one `<ticker>.csv` file per ticker.
+///
+/// Corporate actions are read from `corporate_actions.csv` in the same directory if it exists,
+/// in the format described in [`CorporateActions::from_csv`].
+///
+/// Rows outside of `Options.start` and `Options.end` are skipped. The files are expected to
+/// already be at the requested resolution.
+pub struct CsvProvider {
+    dir: PathBuf,
+    columns: CsvColumns,
+    timestamp_format: TimestampFormat,
+    timezone: Tz,
+    delimiter: u8,
+}
+
+impl CsvProvider {
+    pub fn new<T: Into<PathBuf>>(dir: T) -> Self {
+        Self {
+            dir: dir.into(),
+            columns: CsvColumns::default(),
+            timestamp_format: TimestampFormat::Rfc3339,
+            timezone: Eastern,
+            delimiter: b',',
+        }
+    }
+
+    pub fn columns(mut self, columns: CsvColumns) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    pub fn timestamp_format(mut self, timestamp_format: TimestampFormat) -> Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    pub fn timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    fn path(&self, ticker: &str) -> PathBuf {
+        let mut path = self.dir.clone();
+        path.push(format!("{}.csv", ticker));
+        path
+    }
+
+    fn parse_datetime(&self, s: &str) -> Result<DateTime<Tz>, String> {
+        let s = s.trim();
+        let datetime = match &self.timestamp_format {
+            TimestampFormat::UnixSeconds => {
+                let secs = i64::from_str(s).map_err(|e| e.to_string())?;
+                Utc.timestamp_opt(secs, 0)
+                    .single()
+                    .ok_or_else(|| format!("Timestamp {} is out of range", s))?
+                    .with_timezone(&self.timezone)
+            }
+            TimestampFormat::UnixMillis => {
+                let millis = i64::from_str(s).map_err(|e| e.to_string())?;
+                Utc.timestamp_millis_opt(millis)
+                    .single()
+                    .ok_or_else(|| format!("Timestamp {} is out of range", s))?
+                    .with_timezone(&self.timezone)
+            }
+            TimestampFormat::Rfc3339 => DateTime::parse_from_rfc3339(s)
+                .map_err(|e| e.to_string())?
+                .with_timezone(&self.timezone),
+            TimestampFormat::DateTime(format) => {
+                let naive = NaiveDateTime::parse_from_str(s, format).map_err(|e| e.to_string())?;
+                self.localize(naive)?
+            }
+            TimestampFormat::Date(format) => {
+                let naive = NaiveDate::parse_from_str(s, format).map_err(|e| e.to_string())?;
+                self.localize(naive.and_hms_opt(0, 0, 0).unwrap())?
+            }
+        };
+        Ok(datetime.with_timezone(&Eastern))
+    }
+
+    fn localize(&self, naive: NaiveDateTime) -> Result<DateTime<Tz>, String> {
+        self.timezone
+            .from_local_datetime(&naive)
+            .single()
+            .ok_or_else(|| format!("{} is ambiguous or invalid in {}", naive, self.timezone))
+    }
+
+    fn read_ticker(&self, ticker: &str, meta: &Options) -> Result<Vec<Aggregate>, Error> {
+        let path = self.path(ticker);
+        let mut reader = ::csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .from_path(&path)
+            .map_err(|e| csv_error(&path, e))?;
+        let headers = reader.headers().map_err(|e| csv_error(&path, e))?.clone();
+        let index = |column: &str| {
+            headers
+                .iter()
+                .position(|h| h.trim() == column)
+                .ok_or_else(|| Error::Parse {
+                    path: path.clone(),
+                    line: 1,
+                    message: format!("Missing column {}", column),
+                })
+        };
+        let datetime_idx = index(&self.columns.datetime)?;
+        let open_idx = index(&self.columns.open)?;
+        let high_idx = index(&self.columns.high)?;
+        let low_idx = index(&self.columns.low)?;
+        let close_idx = index(&self.columns.close)?;
+        let volume_idx = index(&self.columns.volume)?;
+
+        let mut aggregates = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| csv_error(&path, e))?;
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            let parse_error = |message: String| Error::Parse {
+                path: path.clone(),
+                line,
+                message,
+            };
+            let field = |idx: usize| {
+                record
+                    .get(idx)
+                    .ok_or_else(|| parse_error(format!("Missing field {}", idx)))
+            };
+            let decimal = |idx: usize, name: &str| {
+                let value = field(idx)?;
+                Decimal::from_str(value.trim())
+                    .map_err(|e| parse_error(format!("Invalid {} {:?}: {}", name, value, e)))
+            };
+            let datetime = self
+                .parse_datetime(field(datetime_idx)?)
+                .map_err(|e| parse_error(format!("Invalid datetime: {}", e)))?;
+            let date = datetime.date_naive();
+            if date < meta.start || date > meta.end {
+                continue;
+            }
+            aggregates.push(Aggregate {
+                datetime,
+                open: decimal(open_idx, "open")?,
+                high: decimal(high_idx, "high")?,
+                low: decimal(low_idx, "low")?,
+                close: decimal(close_idx, "close")?,
+                volume: decimal(volume_idx, "volume")?,
+            });
+        }
+        aggregates.sort_by_key(|agg| agg.datetime);
+        Ok(aggregates)
+    }
+}
+
+fn csv_error(path: &Path, e: ::csv::Error) -> Error {
+    let line = e.position().map(|p| p.line()).unwrap_or_default();
+    match e.into_kind() {
+        ::csv::ErrorKind::Io(e) => Error::File {
+            path: path.to_path_buf(),
+            source: e,
+        },
+        kind => Error::Parse {
+            path: path.to_path_buf(),
+            line,
+            message: format!("{:?}", kind),
+        },
+    }
+}
+
+#[async_trait]
+impl DataProvider for CsvProvider {
+    async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+        let mut data = MarketData::new();
+        let mut report = DownloadReport::default();
+        for ticker in meta.tickers.iter() {
+            match self.read_ticker(ticker, meta) {
+                Ok(aggregates) => {
+                    data.insert(ticker.clone(), aggregates);
+                }
+                Err(e) => report
+                    .failures
+                    .push(DownloadFailure::from_read_error(ticker, &e)),
+            }
+        }
+        if report.is_empty() {
+            Ok(data)
+        } else {
+            Err(Error::Download(Box::new(DownloadError { report, data })))
+        }
+    }
+
+    async fn download_corporate_actions(&self, meta: &Options) -> Result<CorporateActions, Error> {
+        let path = self.dir.join("corporate_actions.csv");
+        if path.exists() {
+            Ok(CorporateActions::from_csv(path)?.filter(meta))
+        } else {
+            Ok(CorporateActions::default())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::report::DownloadErrorKind;
+    use crate::markets::data_manager::DataManager;
+    use crate::DownloadPolicy;
+    use std::fs::{create_dir_all, write};
+    use uuid::Uuid;
+
+    fn test_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("backtester-{}", Uuid::new_v4()));
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn it_reads_csv_files() {
+        let dir = test_dir();
+        write(
+            dir.join("AAPL.csv"),
+            "date;o;h;l;c;v\n\
+             2021-01-05 09:31;10;11;9;10.5;100\n\
+             2021-01-05 09:30;9;10;8;9.5;200\n\
+             2021-01-07 09:30;9;10;8;9.5;200\n",
+        )
+        .unwrap();
+        let provider = CsvProvider::new(&dir)
+            .delimiter(b';')
+            .columns(CsvColumns {
+                datetime: "date".into(),
+                open: "o".into(),
+                high: "h".into(),
+                low: "l".into(),
+                close: "c".into(),
+                volume: "v".into(),
+            })
+            .timestamp_format(TimestampFormat::DateTime("%Y-%m-%d %H:%M".into()));
+        let options = Options::new(
+            vec!["AAPL".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+        );
+        let data = provider.download_data(&options).await.unwrap();
+        let aggregates = &data["AAPL"];
+        assert_eq!(aggregates.len(), 2);
+        assert_eq!(
+            aggregates[0].datetime,
+            Eastern.with_ymd_and_hms(2021, 1, 5, 9, 30, 0).unwrap()
+        );
+        assert_eq!(aggregates[0].close, Decimal::new(95, 1));
+        assert_eq!(aggregates[1].volume, Decimal::new(100, 0));
+    }
+
+    #[tokio::test]
+    async fn it_reports_malformed_rows() {
+        let dir = test_dir();
+        write(
+            dir.join("AAPL.csv"),
+            "datetime,open,high,low,close,volume\n\
+             1609857000,10,11,9,10.5,100\n\
+             1609857060,10,eleven,9,10.5,100\n",
+        )
+        .unwrap();
+        let provider = CsvProvider::new(&dir).timestamp_format(TimestampFormat::UnixSeconds);
+        let options = Options::new(
+            vec!["AAPL".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 12, 31).unwrap(),
+        );
+        match provider.download_data(&options).await {
+            Err(Error::Download(e)) => {
+                let failure = &e.report.failures[0];
+                assert_eq!(failure.ticker, "AAPL");
+                assert_eq!(failure.kind, DownloadErrorKind::InvalidData);
+                let location = format!("{}:3:", dir.join("AAPL.csv").display());
+                assert!(failure.message.starts_with(&location));
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_continues_without_missing_files() {
+        let dir = test_dir();
+        write(
+            dir.join("AAPL.csv"),
+            "datetime,open,high,low,close,volume\n\
+             1609857000,10,11,9,10.5,100\n",
+        )
+        .unwrap();
+        let provider = CsvProvider::new(&dir).timestamp_format(TimestampFormat::UnixSeconds);
+        let options = Options::new(
+            vec!["AAPL".into(), "MSFT".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+        )
+        .set_download_policy(DownloadPolicy::Warn);
+        let mut data_manager = DataManager::new(options, Box::new(provider));
+        let report = data_manager.download_data().await.unwrap();
+        assert_eq!(report.tickers(), vec!["MSFT".to_string()]);
+        assert_eq!(report.failures[0].kind, DownloadErrorKind::NoData);
+        assert!(data_manager
+            .get_bar(
+                "AAPL",
+                Eastern.with_ymd_and_hms(2021, 1, 5, 9, 30, 0).unwrap()
+            )
+            .is_some());
+    }
+}
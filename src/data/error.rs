@@ -1,12 +1,45 @@
+use super::report::DownloadError;
+use super::validation::ValidationReport;
+use crate::Resolution;
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("{0}")]
     Io(std::io::Error),
+    #[error("{}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{}:{line}: {message}", path.display())]
+    Parse {
+        path: PathBuf,
+        line: u64,
+        message: String,
+    },
+    #[error("{0}")]
+    Encode(rmp_serde::encode::Error),
+    #[error("{0}")]
+    Decode(rmp_serde::decode::Error),
+    #[error("{0}")]
+    Download(Box<DownloadError>),
+    #[error("{0}")]
+    Validation(Box<ValidationReport>),
+    #[error("Resolution {0:?} is not supported by this data provider")]
+    UnsupportedResolution(Resolution),
+    #[error("Environment variable {0} is not set")]
+    MissingEnvVar(String),
     #[cfg(feature = "polygon")]
     #[error("{0}")]
     Polygon(::polygon::errors::Error),
+    #[cfg(feature = "parquet")]
+    #[error("{0}")]
+    Parquet(::parquet::errors::ParquetError),
+    #[cfg(feature = "parquet")]
+    #[error("{0}")]
+    Arrow(::arrow::error::ArrowError),
 }
 
 impl From<std::io::Error> for Error {
@@ -15,9 +48,35 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<rmp_serde::encode::Error> for Error {
+    fn from(e: rmp_serde::encode::Error) -> Self {
+        Self::Encode(e)
+    }
+}
+
+impl From<rmp_serde::decode::Error> for Error {
+    fn from(e: rmp_serde::decode::Error) -> Self {
+        Self::Decode(e)
+    }
+}
+
 #[cfg(feature = "polygon")]
 impl From<::polygon::errors::Error> for Error {
     fn from(e: ::polygon::errors::Error) -> Self {
         Self::Polygon(e)
     }
 }
+
+#[cfg(feature = "parquet")]
+impl From<::parquet::errors::ParquetError> for Error {
+    fn from(e: ::parquet::errors::ParquetError) -> Self {
+        Self::Parquet(e)
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<::arrow::error::ArrowError> for Error {
+    fn from(e: ::arrow::error::ArrowError) -> Self {
+        Self::Arrow(e)
+    }
+}
@@ -0,0 +1,137 @@
+use super::{
+    resample::{resample, Timeframe},
+    Aggregate,
+};
+use crate::utils::exchange_calendar::ExchangeCalendar;
+use chrono::DateTime;
+use chrono_tz::Tz;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The most recent bars of several tickers, aligned on the same datetimes.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    /// The datetime of each bar, in ascending order
+    pub datetimes: Vec<DateTime<Tz>>,
+    /// The bars of each ticker, with `None` where a ticker has no bar at that datetime
+    pub bars: HashMap<String, Vec<Option<Aggregate>>>,
+}
+
+impl History {
+    /// Aligns the bars of each ticker on the last `n_bars` datetimes that any ticker has a bar at.
+    pub(crate) fn align(histories: HashMap<String, Vec<Aggregate>>, n_bars: usize) -> Self {
+        let all: BTreeSet<DateTime<Tz>> = histories
+            .values()
+            .flat_map(|bars| bars.iter().map(|bar| bar.datetime))
+            .collect();
+        let datetimes: Vec<DateTime<Tz>> = all.into_iter().rev().take(n_bars).rev().collect();
+        let bars = histories
+            .into_iter()
+            .map(|(ticker, history)| {
+                let mut by_datetime: HashMap<DateTime<Tz>, Aggregate> =
+                    history.into_iter().map(|bar| (bar.datetime, bar)).collect();
+                let aligned = datetimes
+                    .iter()
+                    .map(|datetime| by_datetime.remove(datetime))
+                    .collect();
+                (ticker, aligned)
+            })
+            .collect();
+        Self { datetimes, bars }
+    }
+}
+
+/// Returns the last `n_bars` bars of the given timeframe up to and including `end`. The series is
+/// walked backwards from `end`, so only the bars that are needed are visited. The last bar may
+/// only cover part of its interval.
+pub(crate) fn last_bars(
+    series: &BTreeMap<DateTime<Tz>, Aggregate>,
+    end: DateTime<Tz>,
+    n_bars: usize,
+    timeframe: Timeframe,
+    calendar: &dyn ExchangeCalendar,
+) -> Vec<Aggregate> {
+    let mut buckets = 0;
+    let mut current = None;
+    let mut bars: Vec<&Aggregate> = Vec::new();
+    for agg in series.range(..=end).map(|(_, agg)| agg).rev() {
+        let bucket = match timeframe.bucket(agg.datetime, calendar) {
+            Some(bucket) => bucket,
+            None => continue,
+        };
+        if current != Some(bucket) {
+            if buckets == n_bars {
+                break;
+            }
+            buckets += 1;
+            current = Some(bucket);
+        }
+        bars.push(agg);
+    }
+    bars.reverse();
+    resample(bars, timeframe, calendar)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::testing::flat_bar;
+    use crate::utils::nyse_calendar::NyseCalendar;
+    use chrono::{Duration, TimeZone};
+    use chrono_tz::US::Eastern;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn it_returns_the_last_bars_across_sessions() {
+        // Minute bars for the last ten minutes of Friday and the first ten of Tuesday, after the
+        // MLK day weekend.
+        let friday = Eastern.with_ymd_and_hms(2021, 1, 15, 15, 50, 0).unwrap();
+        let tuesday = Eastern.with_ymd_and_hms(2021, 1, 19, 9, 30, 0).unwrap();
+        let series: BTreeMap<DateTime<Tz>, Aggregate> = (0..10)
+            .map(|i| flat_bar(friday + Duration::minutes(i), i))
+            .chain((0..10).map(|i| flat_bar(tuesday + Duration::minutes(i), 10 + i)))
+            .map(|agg| (agg.datetime, agg))
+            .collect();
+        let end = tuesday + Duration::minutes(6);
+
+        let bars = last_bars(&series, end, 3, Timeframe::Minutes(5), &NyseCalendar);
+        let datetimes: Vec<DateTime<Tz>> = bars.iter().map(|bar| bar.datetime).collect();
+        assert_eq!(
+            datetimes,
+            vec![
+                friday + Duration::minutes(5),
+                tuesday,
+                tuesday + Duration::minutes(5)
+            ]
+        );
+        // The last bar is still forming
+        assert_eq!(bars[2].close, Decimal::new(16, 0));
+        assert!(last_bars(&series, end, 0, Timeframe::Minutes(5), &NyseCalendar).is_empty());
+    }
+
+    #[test]
+    fn it_aligns_histories() {
+        let opening = Eastern.with_ymd_and_hms(2021, 1, 5, 9, 30, 0).unwrap();
+        let mut histories = HashMap::new();
+        histories.insert(
+            "AAPL".to_string(),
+            (0..3)
+                .map(|i| flat_bar(opening + Duration::minutes(i), i))
+                .collect::<Vec<_>>(),
+        );
+        histories.insert(
+            "TSLA".to_string(),
+            vec![flat_bar(opening + Duration::minutes(1), 1)],
+        );
+        let history = History::align(histories, 2);
+        assert_eq!(
+            history.datetimes,
+            vec![
+                opening + Duration::minutes(1),
+                opening + Duration::minutes(2)
+            ]
+        );
+        assert_eq!(history.bars["AAPL"].len(), 2);
+        assert!(history.bars["TSLA"][0].is_some());
+        assert!(history.bars["TSLA"][1].is_none());
+    }
+}
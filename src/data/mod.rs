@@ -1,11 +1,30 @@
-use crate::utils::serde_tz;
-use chrono::{DateTime, NaiveTime, TimeZone};
-use chrono_tz::{Tz, US::Eastern};
-use polygon::rest::Aggregate as PolygonAggregate;
+use crate::utils::{exchange_calendar::ExchangeCalendar, nyse_calendar::NyseCalendar, serde_tz};
+#[cfg(feature = "polygon")]
+use ::polygon::rest::Aggregate as PolygonAggregate;
+use chrono::{DateTime, TimeZone};
+use chrono_tz::Tz;
 use rust_decimal::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
+pub mod cache;
+pub mod corporate_actions;
+pub mod csv;
 pub mod error;
+pub mod history;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+#[cfg(feature = "polygon")]
+pub mod polygon;
+pub mod provider;
+pub mod report;
+pub mod resample;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod validation;
+
+/// Aggregates keyed by ticker, each series sorted by datetime.
+pub type MarketData = HashMap<String, Vec<Aggregate>>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Aggregate {
@@ -18,10 +37,11 @@ pub struct Aggregate {
     pub volume: Decimal,
 }
 
+#[cfg(feature = "polygon")]
 impl From<PolygonAggregate> for Aggregate {
     fn from(p: PolygonAggregate) -> Aggregate {
         Aggregate {
-            datetime: p.t.with_timezone(&Eastern),
+            datetime: p.t.with_timezone(&chrono_tz::US::Eastern),
             open: p.o,
             high: p.h,
             low: p.l,
@@ -31,22 +51,43 @@ impl From<PolygonAggregate> for Aggregate {
     }
 }
 
+/// Checks of datetimes against the sessions of an exchange. The `_on` methods take the calendar of
+/// the exchange, the others use the NYSE.
 pub trait MarketTimeExt {
-    fn is_regular_hours(&self) -> bool;
-    fn is_opening(&self) -> bool;
-    fn is_closing(&self) -> bool;
-}
+    fn is_regular_hours_on(&self, calendar: &dyn ExchangeCalendar) -> bool;
+    fn is_opening_on(&self, calendar: &dyn ExchangeCalendar) -> bool;
+    fn is_closing_on(&self, calendar: &dyn ExchangeCalendar) -> bool;
 
-impl<T: TimeZone> MarketTimeExt for DateTime<T> {
     fn is_regular_hours(&self) -> bool {
-        let zoned = self.with_timezone(&Eastern);
-        (zoned.time() >= NaiveTime::from_hms(9, 30, 00))
-            && (zoned.time() < NaiveTime::from_hms(16, 00, 00))
+        self.is_regular_hours_on(&NyseCalendar)
     }
     fn is_opening(&self) -> bool {
-        self.with_timezone(&Eastern).time() == NaiveTime::from_hms(9, 30, 0)
+        self.is_opening_on(&NyseCalendar)
     }
     fn is_closing(&self) -> bool {
-        self.with_timezone(&Eastern).time() == NaiveTime::from_hms(16, 0, 0)
+        self.is_closing_on(&NyseCalendar)
+    }
+}
+
+impl<T: TimeZone> MarketTimeExt for DateTime<T> {
+    fn is_regular_hours_on(&self, calendar: &dyn ExchangeCalendar) -> bool {
+        calendar
+            .trading_date(self.with_timezone(&calendar.timezone()))
+            .is_some()
+    }
+    fn is_opening_on(&self, calendar: &dyn ExchangeCalendar) -> bool {
+        let zoned = self.with_timezone(&calendar.timezone());
+        calendar
+            .trading_date(zoned)
+            .and_then(|date| calendar.opening(date))
+            .filter(|opening| *opening == zoned)
+            .is_some()
+    }
+    fn is_closing_on(&self, calendar: &dyn ExchangeCalendar) -> bool {
+        let zoned = self.with_timezone(&calendar.timezone());
+        let date = zoned.naive_local().date();
+        [date, date.pred_opt().unwrap()]
+            .iter()
+            .any(|date| calendar.closing(*date) == Some(zoned))
     }
 }
@@ -0,0 +1,695 @@
+use super::{
+    error::Error,
+    provider::DataProvider,
+    report::{DownloadError, DownloadFailure, DownloadReport},
+    Aggregate, MarketData,
+};
+use crate::Options;
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Date32Type, Decimal128Type, Float64Type, Int64Type, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::{Tz, US::Eastern};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ProjectionMask;
+use parquet::file::statistics::Statistics;
+use rust_decimal::prelude::*;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Names of the columns holding each field of an [`Aggregate`].
+///
+/// The ticker column is only read from files that are not partitioned by ticker.
+#[derive(Clone, Debug)]
+pub struct ParquetColumns {
+    pub ticker: String,
+    pub datetime: String,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    pub volume: String,
+}
+
+impl Default for ParquetColumns {
+    fn default() -> Self {
+        Self {
+            ticker: "ticker".to_string(),
+            datetime: "datetime".to_string(),
+            open: "open".to_string(),
+            high: "high".to_string(),
+            low: "low".to_string(),
+            close: "close".to_string(),
+            volume: "volume".to_string(),
+        }
+    }
+}
+
+/// Reads aggregates from a directory of Parquet files partitioned Hive-style by
+/// `ticker=<TICKER>` and/or `date=<YYYY-MM-DD>` directories.
+///
+/// Partitions and row groups outside of `Options.start` and `Options.end` are skipped without
+/// being decoded, and only the configured columns are read. Datetime columns may be Arrow
+/// timestamps, dates or integers since the Unix epoch in `integer_time_unit`.
+pub struct ParquetProvider {
+    dir: PathBuf,
+    columns: ParquetColumns,
+    integer_time_unit: TimeUnit,
+    timezone: Tz,
+    batch_size: usize,
+}
+
+impl ParquetProvider {
+    pub fn new<T: Into<PathBuf>>(dir: T) -> Self {
+        Self {
+            dir: dir.into(),
+            columns: ParquetColumns::default(),
+            integer_time_unit: TimeUnit::Millisecond,
+            timezone: Eastern,
+            batch_size: 65536,
+        }
+    }
+
+    pub fn columns(mut self, columns: ParquetColumns) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    pub fn integer_time_unit(mut self, integer_time_unit: TimeUnit) -> Self {
+        self.integer_time_unit = integer_time_unit;
+        self
+    }
+
+    /// Timezone used to interpret date columns and timestamps without a timezone.
+    pub fn timezone(mut self, timezone: Tz) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    fn files(&self, meta: &Options) -> Result<Vec<(PathBuf, Option<String>)>, Error> {
+        let mut files = Vec::new();
+        let mut stack = vec![(self.dir.clone(), None)];
+        while let Some((dir, ticker)) = stack.pop() {
+            let entries = std::fs::read_dir(&dir).map_err(|source| Error::File {
+                path: dir.clone(),
+                source,
+            })?;
+            for entry in entries {
+                let path = entry?.path();
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                if path.is_dir() {
+                    if let Some(partition) = name.strip_prefix("ticker=") {
+                        if meta.tickers.iter().any(|t| t == partition) {
+                            stack.push((path.clone(), Some(partition.to_string())));
+                        }
+                    } else if let Some(partition) = name.strip_prefix("date=") {
+                        let date =
+                            NaiveDate::parse_from_str(partition, "%Y-%m-%d").map_err(|e| {
+                                Error::Parse {
+                                    path: path.clone(),
+                                    line: 0,
+                                    message: format!("Invalid date partition: {}", e),
+                                }
+                            })?;
+                        if date >= meta.start && date <= meta.end {
+                            stack.push((path.clone(), ticker.clone()));
+                        }
+                    } else {
+                        stack.push((path.clone(), ticker.clone()));
+                    }
+                } else if name.ends_with(".parquet") {
+                    files.push((path, ticker.clone()));
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn read_file(
+        &self,
+        path: &Path,
+        ticker: Option<&str>,
+        meta: &Options,
+        data: &mut MarketData,
+    ) -> Result<(), Error> {
+        let file = File::open(path).map_err(|source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+        let schema = builder.schema().clone();
+        let column_index = |name: &str| {
+            schema.index_of(name).map_err(|_| Error::Parse {
+                path: path.to_path_buf(),
+                line: 0,
+                message: format!("Missing column {}", name),
+            })
+        };
+        let mut names = vec![
+            &self.columns.datetime,
+            &self.columns.open,
+            &self.columns.high,
+            &self.columns.low,
+            &self.columns.close,
+            &self.columns.volume,
+        ];
+        if ticker.is_none() {
+            names.push(&self.columns.ticker);
+        }
+        let indices = names
+            .iter()
+            .map(|name| column_index(name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Bounds on the raw datetime values, used to prune row groups from their statistics. Rows
+        // are kept by their date in Eastern time, so the bounds are Eastern midnights.
+        let datetime_idx = indices[0];
+        let start = eastern_midnight(meta.start);
+        let end = eastern_midnight(meta.end + Duration::days(1));
+        let bounds = match schema.field(datetime_idx).data_type() {
+            // Dates are localized in `timezone`, which can put them on the Eastern day before or
+            // after
+            DataType::Date32 => Some((
+                RawBound::Int32(days_since_epoch(meta.start) - 1),
+                RawBound::Int32(days_since_epoch(meta.end) + 2),
+            )),
+            // Timestamps without a timezone hold wall-clock times in `timezone`
+            DataType::Timestamp(unit, None) => {
+                let wall_clock = |datetime: DateTime<Tz>| {
+                    Utc.from_utc_datetime(&datetime.with_timezone(&self.timezone).naive_local())
+                };
+                Some((
+                    RawBound::Int64(to_unit(wall_clock(start), unit)),
+                    RawBound::Int64(to_unit(wall_clock(end), unit)),
+                ))
+            }
+            DataType::Timestamp(unit, Some(_)) => Some((
+                RawBound::Int64(to_unit(start, unit)),
+                RawBound::Int64(to_unit(end, unit)),
+            )),
+            DataType::Int64 => Some((
+                RawBound::Int64(to_unit(start, &self.integer_time_unit)),
+                RawBound::Int64(to_unit(end, &self.integer_time_unit)),
+            )),
+            _ => None,
+        };
+        let mut row_groups = Vec::new();
+        let mut rows = RowNumbers(Vec::new());
+        let mut first_row = 0;
+        for (i, rg) in builder.metadata().row_groups().iter().enumerate() {
+            let stats = rg.column(datetime_idx).statistics();
+            let overlapping = match (bounds, stats) {
+                (Some((RawBound::Int64(lo), RawBound::Int64(hi))), Some(Statistics::Int64(s))) => {
+                    overlaps(s.min_opt(), s.max_opt(), lo, hi)
+                }
+                (Some((RawBound::Int32(lo), RawBound::Int32(hi))), Some(Statistics::Int32(s))) => {
+                    overlaps(s.min_opt(), s.max_opt(), lo, hi)
+                }
+                _ => true,
+            };
+            let num_rows = rg.num_rows() as u64;
+            if overlapping {
+                row_groups.push(i);
+                rows.0.push((first_row, num_rows));
+            }
+            first_row += num_rows;
+        }
+
+        let mask = ProjectionMask::roots(builder.parquet_schema(), indices);
+        let reader = builder
+            .with_projection(mask)
+            .with_row_groups(row_groups)
+            .with_batch_size(self.batch_size)
+            .build()?;
+        let mut offset = 0;
+        for batch in reader {
+            let batch = batch?;
+            let line = |row: usize| rows.line(offset + row as u64);
+            self.read_batch(&batch, path, ticker, meta, data, &line)?;
+            offset += batch.num_rows() as u64;
+        }
+        Ok(())
+    }
+
+    fn read_batch(
+        &self,
+        batch: &RecordBatch,
+        path: &Path,
+        ticker: Option<&str>,
+        meta: &Options,
+        data: &mut MarketData,
+        line: &dyn Fn(usize) -> u64,
+    ) -> Result<(), Error> {
+        let column = |name: &str| {
+            batch.column_by_name(name).ok_or_else(|| Error::Parse {
+                path: path.to_path_buf(),
+                line: 0,
+                message: format!("Missing column {}", name),
+            })
+        };
+        let datetimes = self.datetimes(column(&self.columns.datetime)?)?;
+        let open = decimals(column(&self.columns.open)?)?;
+        let high = decimals(column(&self.columns.high)?)?;
+        let low = decimals(column(&self.columns.low)?)?;
+        let close = decimals(column(&self.columns.close)?)?;
+        let volume = decimals(column(&self.columns.volume)?)?;
+        let tickers = match ticker {
+            Some(_) => None,
+            None => Some(cast(column(&self.columns.ticker)?, &DataType::Utf8)?),
+        };
+        for row in 0..batch.num_rows() {
+            let ticker = match (ticker, &tickers) {
+                (Some(ticker), _) => ticker,
+                (None, Some(tickers)) => {
+                    let tickers = tickers.as_string::<i32>();
+                    if tickers.is_null(row) {
+                        continue;
+                    }
+                    tickers.value(row)
+                }
+                (None, None) => unreachable!(),
+            };
+            if !meta.tickers.iter().any(|t| t == ticker) {
+                continue;
+            }
+            let fields = (
+                datetimes[row],
+                open[row],
+                high[row],
+                low[row],
+                close[row],
+                volume[row],
+            );
+            let (datetime, open, high, low, close, volume) = match fields {
+                (Some(d), Some(o), Some(h), Some(l), Some(c), Some(v)) => (d, o, h, l, c, v),
+                _ => {
+                    return Err(Error::Parse {
+                        path: path.to_path_buf(),
+                        line: line(row),
+                        message: "Missing or invalid value in aggregate".to_string(),
+                    })
+                }
+            };
+            let date = datetime.date_naive();
+            if date < meta.start || date > meta.end {
+                continue;
+            }
+            data.entry(ticker.to_string()).or_default().push(Aggregate {
+                datetime,
+                open,
+                high,
+                low,
+                close,
+                volume,
+            });
+        }
+        Ok(())
+    }
+
+    fn datetimes(&self, array: &ArrayRef) -> Result<Vec<Option<DateTime<Tz>>>, Error> {
+        let (unit, wall_clock) = match array.data_type() {
+            DataType::Date32 => {
+                let days = array.as_primitive::<Date32Type>();
+                return Ok(days
+                    .iter()
+                    .map(|d| {
+                        d.map(|d| {
+                            let date = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
+                                + Duration::days(d as i64);
+                            self.localize(date).with_timezone(&Eastern)
+                        })
+                    })
+                    .collect());
+            }
+            DataType::Timestamp(unit, timezone) => (*unit, timezone.is_none()),
+            _ => (self.integer_time_unit, false),
+        };
+        let raw = cast(array, &DataType::Int64)?;
+        let raw = raw.as_primitive::<Int64Type>();
+        Ok(raw
+            .iter()
+            .map(|v| {
+                v.and_then(|v| from_unit(v, &unit)).map(|utc| {
+                    if wall_clock {
+                        self.timezone
+                            .from_local_datetime(&utc.naive_utc())
+                            .earliest()
+                            .unwrap_or_else(|| utc.with_timezone(&self.timezone))
+                            .with_timezone(&Eastern)
+                    } else {
+                        utc.with_timezone(&Eastern)
+                    }
+                })
+            })
+            .collect())
+    }
+
+    fn localize(&self, date: NaiveDate) -> DateTime<Tz> {
+        self.timezone
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+            .expect("Midnight exists in all supported timezones")
+    }
+}
+
+/// The row in the file of the first row read from each of the row groups that weren't pruned,
+/// and the number of rows in that group.
+struct RowNumbers(Vec<(u64, u64)>);
+
+impl RowNumbers {
+    /// Returns the row in the file of the `row`th row read.
+    fn line(&self, mut row: u64) -> u64 {
+        for (first_row, num_rows) in self.0.iter() {
+            if row < *num_rows {
+                return first_row + row;
+            }
+            row -= num_rows;
+        }
+        row
+    }
+}
+
+#[derive(Clone, Copy)]
+enum RawBound {
+    Int32(i32),
+    Int64(i64),
+}
+
+fn overlaps<T: PartialOrd>(min: Option<&T>, max: Option<&T>, lo: T, hi: T) -> bool {
+    match (min, max) {
+        (Some(min), Some(max)) => *max >= lo && *min < hi,
+        _ => true,
+    }
+}
+
+fn eastern_midnight(date: NaiveDate) -> DateTime<Tz> {
+    Eastern
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        .expect("Midnight exists in Eastern time")
+}
+
+fn days_since_epoch(date: NaiveDate) -> i32 {
+    (date - NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()).num_days() as i32
+}
+
+fn to_unit<T: TimeZone>(datetime: DateTime<T>, unit: &TimeUnit) -> i64 {
+    match unit {
+        TimeUnit::Second => datetime.timestamp(),
+        TimeUnit::Millisecond => datetime.timestamp_millis(),
+        TimeUnit::Microsecond => datetime.timestamp_micros(),
+        // Nanoseconds only cover the years 1677 to 2262
+        TimeUnit::Nanosecond => {
+            datetime
+                .timestamp_nanos_opt()
+                .unwrap_or(if datetime.timestamp() < 0 {
+                    i64::MIN
+                } else {
+                    i64::MAX
+                })
+        }
+    }
+}
+
+/// Returns `None` if the value is out of the range of datetimes.
+fn from_unit(value: i64, unit: &TimeUnit) -> Option<DateTime<Utc>> {
+    match unit {
+        TimeUnit::Second => Utc.timestamp_opt(value, 0).single(),
+        TimeUnit::Millisecond => Utc.timestamp_millis_opt(value).single(),
+        TimeUnit::Microsecond => value
+            .checked_mul(1_000)
+            .map(|nanos| Utc.timestamp_nanos(nanos)),
+        TimeUnit::Nanosecond => Some(Utc.timestamp_nanos(value)),
+    }
+}
+
+fn decimals(array: &ArrayRef) -> Result<Vec<Option<Decimal>>, Error> {
+    if let DataType::Decimal128(_, scale) = array.data_type() {
+        let scale = *scale as u32;
+        return Ok(array
+            .as_primitive::<Decimal128Type>()
+            .iter()
+            .map(|v| v.map(|v| Decimal::from_i128_with_scale(v, scale)))
+            .collect());
+    }
+    let floats = cast(array, &DataType::Float64)?;
+    Ok(floats
+        .as_primitive::<Float64Type>()
+        .iter()
+        .map(|v| v.and_then(Decimal::from_f64))
+        .collect())
+}
+
+#[async_trait]
+impl DataProvider for ParquetProvider {
+    async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+        let mut data = MarketData::new();
+        let mut report = DownloadReport::default();
+        for (path, ticker) in self.files(meta)? {
+            // Rows of a file that can't be read entirely are discarded
+            let mut file_data = MarketData::new();
+            match self.read_file(&path, ticker.as_deref(), meta, &mut file_data) {
+                Ok(()) => {
+                    for (ticker, aggregates) in file_data {
+                        data.entry(ticker).or_default().extend(aggregates);
+                    }
+                }
+                // A file outside of a ticker partition may hold rows for any of the tickers
+                Err(e) => match ticker {
+                    Some(ticker) => report
+                        .failures
+                        .push(DownloadFailure::from_read_error(&ticker, &e)),
+                    None => report.failures.extend(
+                        meta.tickers
+                            .iter()
+                            .map(|ticker| DownloadFailure::from_read_error(ticker, &e)),
+                    ),
+                },
+            }
+        }
+        for aggregates in data.values_mut() {
+            aggregates.sort_by_key(|agg| agg.datetime);
+        }
+        if report.is_empty() {
+            Ok(data)
+        } else {
+            Err(Error::Download(Box::new(DownloadError { report, data })))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::report::DownloadErrorKind;
+    use arrow::array::{Float64Array, Int64Array, StringArray, TimestampMillisecondArray};
+    use arrow::datatypes::{Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::properties::WriterProperties;
+    use std::fs::create_dir_all;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    fn write_file(path: &Path, tickers: Vec<&str>, datetimes: Vec<i64>) {
+        let closes = vec![Some(1.5); tickers.len()];
+        write_rows(path, Some(tickers), datetimes, closes);
+    }
+
+    /// Writes one row group per row, leaving out the ticker column if `tickers` is `None`.
+    fn write_rows(
+        path: &Path,
+        tickers: Option<Vec<&str>>,
+        datetimes: Vec<i64>,
+        closes: Vec<Option<f64>>,
+    ) {
+        create_dir_all(path.parent().unwrap()).unwrap();
+        let n = datetimes.len();
+        let prices: ArrayRef = Arc::new(Float64Array::from(vec![1.5; n]));
+        let mut fields = vec![
+            Field::new(
+                "datetime",
+                DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                false,
+            ),
+            Field::new("open", DataType::Float64, false),
+            Field::new("high", DataType::Float64, false),
+            Field::new("low", DataType::Float64, false),
+            Field::new("close", DataType::Float64, true),
+            Field::new("volume", DataType::Int64, false),
+            Field::new("vwap", DataType::Float64, false),
+        ];
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(TimestampMillisecondArray::from(datetimes).with_timezone("UTC")),
+            prices.clone(),
+            prices.clone(),
+            prices.clone(),
+            Arc::new(Float64Array::from(closes)),
+            Arc::new(Int64Array::from(vec![100; n])),
+            prices,
+        ];
+        if let Some(tickers) = tickers {
+            fields.insert(0, Field::new("ticker", DataType::Utf8, false));
+            columns.insert(0, Arc::new(StringArray::from(tickers)));
+        }
+        let schema = Arc::new(Schema::new(fields));
+        let batch = RecordBatch::try_new(schema.clone(), columns).unwrap();
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(1)
+            .build();
+        let file = File::create(path).unwrap();
+        let mut writer = ArrowWriter::try_new(file, schema, Some(props)).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    fn temp_dir() -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("backtester-{}", Uuid::new_v4()));
+        dir
+    }
+
+    #[tokio::test]
+    async fn it_reads_partitioned_parquet_files() {
+        let dir = temp_dir();
+        let jan_5 = Eastern
+            .with_ymd_and_hms(2021, 1, 5, 9, 30, 0)
+            .unwrap()
+            .timestamp_millis();
+        let jan_6 = Eastern
+            .with_ymd_and_hms(2021, 1, 6, 9, 30, 0)
+            .unwrap()
+            .timestamp_millis();
+        write_file(
+            &dir.join("date=2021-01-05/part-0.parquet"),
+            vec!["AAPL", "MSFT", "AAPL"],
+            vec![jan_5 + 60_000, jan_5, jan_5],
+        );
+        write_file(
+            &dir.join("date=2021-01-06/part-0.parquet"),
+            vec!["AAPL"],
+            vec![jan_6],
+        );
+        write_file(
+            &dir.join("date=not-a-date/part-0.parquet"),
+            vec!["AAPL"],
+            vec![jan_6],
+        );
+
+        let provider = ParquetProvider::new(&dir);
+        let options = Options::new(
+            vec!["AAPL".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+        );
+        assert!(matches!(
+            provider.download_data(&options).await,
+            Err(Error::Parse { .. })
+        ));
+
+        std::fs::remove_dir_all(dir.join("date=not-a-date")).unwrap();
+        let data = provider.download_data(&options).await.unwrap();
+        assert_eq!(data.len(), 1);
+        let aggregates = &data["AAPL"];
+        assert_eq!(aggregates.len(), 2);
+        assert_eq!(
+            aggregates[0].datetime,
+            Eastern.with_ymd_and_hms(2021, 1, 5, 9, 30, 0).unwrap()
+        );
+        assert_eq!(aggregates[0].close, Decimal::new(15, 1));
+        assert_eq!(aggregates[1].volume, Decimal::new(100, 0));
+    }
+
+    #[tokio::test]
+    async fn it_reads_ticker_partitions() {
+        let dir = temp_dir();
+        let opening = Eastern
+            .with_ymd_and_hms(2021, 1, 5, 9, 30, 0)
+            .unwrap()
+            .timestamp_millis();
+        for ticker in ["AAPL", "MSFT", "TSLA"] {
+            write_rows(
+                &dir.join(format!("ticker={}/part-0.parquet", ticker)),
+                None,
+                vec![opening, opening + 60_000],
+                vec![Some(1.5), Some(2.5)],
+            );
+        }
+
+        let provider = ParquetProvider::new(&dir);
+        let options = Options::new(
+            vec!["AAPL".into(), "TSLA".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+        );
+        let data = provider.download_data(&options).await.unwrap();
+        let mut tickers: Vec<&String> = data.keys().collect();
+        tickers.sort();
+        assert_eq!(tickers, vec!["AAPL", "TSLA"]);
+        assert_eq!(data["TSLA"].len(), 2);
+        assert_eq!(data["TSLA"][1].close, Decimal::new(25, 1));
+    }
+
+    #[tokio::test]
+    async fn it_prunes_row_groups_by_eastern_date() {
+        let dir = temp_dir();
+        let path = dir.join("part-0.parquet");
+        // The evening bar is on the next day in UTC, and the null close of the bar on the next
+        // day would fail the download if its row group were read.
+        let evening = Eastern
+            .with_ymd_and_hms(2021, 1, 5, 20, 0, 0)
+            .unwrap()
+            .timestamp_millis();
+        let next_day = Eastern
+            .with_ymd_and_hms(2021, 1, 6, 9, 30, 0)
+            .unwrap()
+            .timestamp_millis();
+        write_rows(
+            &path,
+            Some(vec!["AAPL", "AAPL"]),
+            vec![evening, next_day],
+            vec![Some(1.5), None],
+        );
+
+        let provider = ParquetProvider::new(&dir).timezone(chrono_tz::UTC);
+        let options = Options::new(
+            vec!["AAPL".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+        );
+        let data = provider.download_data(&options).await.unwrap();
+        assert_eq!(data["AAPL"].len(), 1);
+        assert_eq!(
+            data["AAPL"][0].datetime,
+            Eastern.with_ymd_and_hms(2021, 1, 5, 20, 0, 0).unwrap()
+        );
+
+        // The row of the null close is reported by its position in the file
+        let options = Options::new(
+            vec!["AAPL".into()],
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+        );
+        match provider.download_data(&options).await {
+            Err(Error::Download(e)) => {
+                assert_eq!(e.report.failures.len(), 1);
+                let failure = &e.report.failures[0];
+                assert_eq!(failure.ticker, "AAPL");
+                assert_eq!(failure.kind, DownloadErrorKind::InvalidData);
+                assert!(failure.message.contains("part-0.parquet:1:"));
+                assert!(e.data.is_empty());
+            }
+            _ => panic!("Expected a download error"),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use super::{
+    error::Error,
+    provider::DataProvider,
+    report::{DownloadError, DownloadErrorKind, DownloadFailure, DownloadReport},
+    Aggregate, MarketData,
+};
+use crate::{Options, Resolution};
+use async_trait::async_trait;
+use futures::{stream, StreamExt};
+use polygon::rest::{client, GetAggregate, Timespan};
+
+/// Downloads aggregates from the Polygon REST API.
+pub struct PolygonProvider {
+    token: String,
+}
+
+impl PolygonProvider {
+    pub fn new<T: ToString>(token: T) -> Self {
+        Self {
+            token: token.to_string(),
+        }
+    }
+
+    /// Creates a provider using the token in the `POLYGON_TOKEN` environment variable.
+    pub fn from_env() -> Result<Self, Error> {
+        let token = std::env::var("POLYGON_TOKEN")
+            .map_err(|_| Error::MissingEnvVar("POLYGON_TOKEN".to_string()))?;
+        Ok(Self::new(token))
+    }
+}
+
+#[async_trait]
+impl DataProvider for PolygonProvider {
+    async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+        // Intraday resolutions are resampled from minute bars by the `DataManager`, as Polygon's
+        // hourly bars start on the hour rather than at the open.
+        let timespan = match meta.resolution {
+            Resolution::Day => Timespan::Day,
+            Resolution::Minute | Resolution::Hour => Timespan::Minute,
+            Resolution::Custom(step) if step.num_milliseconds() % 60_000 == 0 => Timespan::Minute,
+            Resolution::Second | Resolution::Custom(_) => {
+                return Err(Error::UnsupportedResolution(meta.resolution))
+            }
+        };
+        let jobs: Vec<GetAggregate> = meta
+            .tickers
+            .iter()
+            .map(|ticker| {
+                let start = meta.start.and_hms_opt(0, 0, 0).unwrap();
+                let end = meta.end.and_hms_opt(0, 0, 0).unwrap();
+                GetAggregate::new(ticker, start, end)
+                    .timespan(timespan)
+                    .limit(50000)
+            })
+            .collect();
+        let client = client(&self.token).show_progress();
+        let streams = client
+            .send_all_paginated(jobs.iter())
+            .zip(meta.tickers.iter())
+            .map(|(stream, ticker)| {
+                stream
+                    .enumerate()
+                    .map(move |(page, result)| (ticker.clone(), page, result))
+            });
+        let mut pages = stream::select_all(streams);
+        let mut market_data = MarketData::new();
+        let mut report = DownloadReport::default();
+        while let Some((ticker, page, result)) = pages.next().await {
+            match result {
+                Ok(wrapper) => market_data
+                    .entry(ticker)
+                    .or_default()
+                    .extend(wrapper.results.into_iter().map(Aggregate::from)),
+                Err(e) => report.failures.push(DownloadFailure {
+                    ticker,
+                    page: Some(page),
+                    kind: DownloadErrorKind::Request,
+                    message: e.to_string(),
+                }),
+            }
+        }
+        for aggregates in market_data.values_mut() {
+            aggregates.sort_by_key(|agg| agg.datetime);
+        }
+        if report.is_empty() {
+            Ok(market_data)
+        } else {
+            Err(Error::Download(Box::new(DownloadError {
+                report,
+                data: market_data,
+            })))
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use super::{corporate_actions::CorporateActions, error::Error, MarketData};
+use crate::Options;
+use async_trait::async_trait;
+
+/// A source of historical market data.
+///
+/// Implementors return the aggregates for every ticker in `meta.tickers` between `meta.start`
+/// and `meta.end` at `meta.resolution`. The series for each ticker is expected to be sorted by
+/// datetime. If only some of the data could be retrieved, implementors should return
+/// [`Error::Download`] with the data that was retrieved and a report of what failed, so that the
+/// caller can apply its [`DownloadPolicy`](crate::DownloadPolicy).
+#[async_trait]
+pub trait DataProvider: Send + Sync {
+    async fn download_data(&self, meta: &Options) -> Result<MarketData, Error>;
+
+    /// Splits and dividends for `meta.tickers` with an ex-date between `meta.start` and
+    /// `meta.end`. Providers without corporate action data return none.
+    async fn download_corporate_actions(&self, _meta: &Options) -> Result<CorporateActions, Error> {
+        Ok(CorporateActions::default())
+    }
+}
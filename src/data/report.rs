@@ -0,0 +1,103 @@
+use super::{error::Error, MarketData};
+use serde::Serialize;
+use std::fmt;
+use std::io;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadErrorKind {
+    /// The request to the data source failed, e.g. due to the network or authentication.
+    Request,
+    /// The response could not be interpreted.
+    InvalidData,
+    /// No data was returned for the ticker.
+    NoData,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DownloadFailure {
+    pub ticker: String,
+    /// The page of a paginated download that failed, if applicable.
+    pub page: Option<usize>,
+    pub kind: DownloadErrorKind,
+    pub message: String,
+}
+
+impl DownloadFailure {
+    /// The failure to read the data of `ticker` from a local file.
+    pub(crate) fn from_read_error(ticker: &str, error: &Error) -> Self {
+        let kind = match error {
+            Error::File { source, .. } if source.kind() == io::ErrorKind::NotFound => {
+                DownloadErrorKind::NoData
+            }
+            Error::Io(_) | Error::File { .. } => DownloadErrorKind::Request,
+            _ => DownloadErrorKind::InvalidData,
+        };
+        Self {
+            ticker: ticker.to_string(),
+            page: None,
+            kind,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DownloadFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({:?}", self.ticker, self.kind)?;
+        if let Some(page) = self.page {
+            write!(f, ", page {}", page)?;
+        }
+        write!(f, "): {}", self.message)
+    }
+}
+
+/// Failures encountered while downloading data.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DownloadReport {
+    pub failures: Vec<DownloadFailure>,
+}
+
+impl DownloadReport {
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// The distinct tickers with at least one failure.
+    pub fn tickers(&self) -> Vec<String> {
+        let mut tickers: Vec<String> = self.failures.iter().map(|f| f.ticker.clone()).collect();
+        tickers.sort();
+        tickers.dedup();
+        tickers
+    }
+}
+
+impl fmt::Display for DownloadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} download failure(s)", self.failures.len())?;
+        for failure in self.failures.iter() {
+            write!(f, "\n  {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+/// A download that only partially succeeded, along with the data that was retrieved.
+pub struct DownloadError {
+    pub report: DownloadReport,
+    pub data: MarketData,
+}
+
+impl fmt::Debug for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DownloadError")
+            .field("report", &self.report)
+            .finish()
+    }
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.report)
+    }
+}
@@ -0,0 +1,318 @@
+use super::Aggregate;
+use crate::utils::exchange_calendar::ExchangeCalendar;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// The bar size to consolidate data into.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum Timeframe {
+    Minutes(u32),
+    Hours(u32),
+    Day,
+    Week,
+    Month,
+}
+
+impl Timeframe {
+    /// Returns the trading day and index of the bar that `datetime` falls into, or `None` if it
+    /// is outside of the exchange's sessions.
+    pub(crate) fn bucket(
+        &self,
+        datetime: DateTime<Tz>,
+        calendar: &dyn ExchangeCalendar,
+    ) -> Option<(NaiveDate, i64)> {
+        let intraday = |size: i64| {
+            let date = calendar.trading_date(datetime)?;
+            let minutes = (datetime - calendar.opening(date)?).num_minutes();
+            Some((date, minutes / size.max(1)))
+        };
+        let daily = || {
+            let local = datetime.with_timezone(&calendar.timezone());
+            let date = local.date_naive();
+            // Daily bars are stamped at midnight and cover the session of their date
+            if local.time() == NaiveTime::from_hms_opt(0, 0, 0).unwrap()
+                && calendar.is_trading_day(date)
+            {
+                Some(date)
+            } else {
+                calendar.trading_date(datetime)
+            }
+        };
+        match self {
+            Self::Minutes(n) => intraday(*n as i64),
+            Self::Hours(n) => intraday(*n as i64 * 60),
+            Self::Day => Some((daily()?, 0)),
+            Self::Week => {
+                let date = daily()?;
+                Some((
+                    date - Duration::days(date.weekday().num_days_from_monday() as i64),
+                    0,
+                ))
+            }
+            Self::Month => Some((daily()?.with_day(1).unwrap(), 0)),
+        }
+    }
+
+    /// The time a bar is labelled with. Intraday bars are labelled with the start of their
+    /// interval, longer bars with the first bar they contain.
+    fn label(
+        &self,
+        bucket: (NaiveDate, i64),
+        first: DateTime<Tz>,
+        calendar: &dyn ExchangeCalendar,
+    ) -> DateTime<Tz> {
+        let (date, index) = bucket;
+        let size = match self {
+            Self::Minutes(n) => *n as i64,
+            Self::Hours(n) => *n as i64 * 60,
+            Self::Day | Self::Week | Self::Month => return first,
+        };
+        let opening = calendar.opening(date).expect("Bucket is on a trading day");
+        (opening + Duration::minutes(index * size.max(1))).with_timezone(&first.timezone())
+    }
+}
+
+/// Consolidates bars, sorted by datetime, into bars of the given timeframe. Bars outside of the
+/// exchange's sessions are skipped, and the last bar may only cover part of its interval.
+pub fn resample<'a, I: IntoIterator<Item = &'a Aggregate>>(
+    bars: I,
+    timeframe: Timeframe,
+    calendar: &dyn ExchangeCalendar,
+) -> Vec<Aggregate> {
+    consolidate(bars, |datetime| {
+        let bucket = timeframe.bucket(datetime, calendar)?;
+        Some((bucket, timeframe.label(bucket, datetime, calendar)))
+    })
+}
+
+/// Consolidates bars, sorted by datetime, into bars of `step` on the grid the simulation clock
+/// ticks on, where the pre-market, the regular session and after-hours are each divided into steps
+/// from their start. Bars outside of the simulated sessions are skipped.
+pub(crate) fn resample_sessions<'a, I: IntoIterator<Item = &'a Aggregate>>(
+    bars: I,
+    step: Duration,
+    calendar: &dyn ExchangeCalendar,
+    extended_hours: bool,
+) -> Vec<Aggregate> {
+    let step = step.num_milliseconds().max(1);
+    consolidate(bars, |datetime| {
+        let local = datetime.with_timezone(&calendar.timezone());
+        let date = local.date_naive();
+        let start = [date, date.succ_opt().unwrap(), date.pred_opt().unwrap()]
+            .iter()
+            .find_map(|date| {
+                let (opening, closing) = if extended_hours {
+                    (
+                        calendar.extended_opening(*date)?,
+                        calendar.extended_closing(*date)?,
+                    )
+                } else {
+                    (calendar.opening(*date)?, calendar.closing(*date)?)
+                };
+                if !(opening..closing).contains(&local) {
+                    return None;
+                }
+                // The regular session and after-hours start their own steps
+                [calendar.opening(*date)?, calendar.closing(*date)?]
+                    .iter()
+                    .copied()
+                    .filter(|boundary| *boundary <= local)
+                    .max()
+                    .or(Some(opening))
+            })?;
+        let steps = (local - start).num_milliseconds() / step;
+        let label =
+            (start + Duration::milliseconds(steps * step)).with_timezone(&datetime.timezone());
+        Some((label, label))
+    })
+}
+
+/// Consolidates bars, sorted by datetime, into one bar per trading day stamped at the opening of
+/// its session, which is when the simulation clock ticks at daily resolution. Daily bars stamped
+/// at midnight are moved to the opening of their session.
+pub(crate) fn resample_days<'a, I: IntoIterator<Item = &'a Aggregate>>(
+    bars: I,
+    calendar: &dyn ExchangeCalendar,
+) -> Vec<Aggregate> {
+    consolidate(bars, |datetime| {
+        let (date, _) = Timeframe::Day.bucket(datetime, calendar)?;
+        let opening = calendar.opening(date)?.with_timezone(&datetime.timezone());
+        Some((date, opening))
+    })
+}
+
+/// Merges consecutive bars that `bucket` assigns to the same bucket, labelling each merged bar
+/// with the label of its bucket's first bar. Bars without a bucket are skipped.
+fn consolidate<'a, I, K, F>(bars: I, bucket: F) -> Vec<Aggregate>
+where
+    I: IntoIterator<Item = &'a Aggregate>,
+    K: Copy + PartialEq,
+    F: Fn(DateTime<Tz>) -> Option<(K, DateTime<Tz>)>,
+{
+    let mut resampled: Vec<Aggregate> = Vec::new();
+    let mut current: Option<K> = None;
+    for agg in bars {
+        let (key, label) = match bucket(agg.datetime) {
+            Some(bucket) => bucket,
+            None => continue,
+        };
+        match resampled.last_mut() {
+            Some(last) if current == Some(key) => {
+                last.high = last.high.max(agg.high);
+                last.low = last.low.min(agg.low);
+                last.close = agg.close;
+                last.volume += agg.volume;
+            }
+            _ => {
+                current = Some(key);
+                resampled.push(Aggregate {
+                    datetime: label,
+                    ..agg.clone()
+                })
+            }
+        }
+    }
+    resampled
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::testing::bar;
+    use crate::utils::nyse_calendar::NyseCalendar;
+    use chrono::TimeZone;
+    use chrono_tz::US::Eastern;
+    use rust_decimal::Decimal;
+
+    /// A bar trading one either side of `price`.
+    fn priced(datetime: DateTime<Tz>, price: i64) -> Aggregate {
+        bar(datetime, price, price + 1, price - 1, price)
+    }
+
+    #[test]
+    fn it_resamples_intraday_bars() {
+        let date = |hour, minute, second| {
+            Eastern
+                .with_ymd_and_hms(2021, 1, 5, hour, minute, second)
+                .unwrap()
+        };
+        let bars = vec![
+            priced(date(9, 29, 0), 1),
+            priced(date(9, 30, 0), 10),
+            priced(date(9, 33, 0), 12),
+            priced(date(9, 34, 0), 11),
+            priced(date(9, 36, 0), 20),
+            priced(date(15, 59, 0), 30),
+            priced(date(16, 0, 0), 40),
+        ];
+        let resampled = resample(&bars, Timeframe::Minutes(5), &NyseCalendar);
+        assert_eq!(resampled.len(), 3);
+        assert_eq!(resampled[0].datetime, date(9, 30, 0));
+        assert_eq!(resampled[0].open, Decimal::new(10, 0));
+        assert_eq!(resampled[0].high, Decimal::new(13, 0));
+        assert_eq!(resampled[0].low, Decimal::new(9, 0));
+        assert_eq!(resampled[0].close, Decimal::new(11, 0));
+        assert_eq!(resampled[0].volume, Decimal::new(3, 0));
+        assert_eq!(resampled[1].datetime, date(9, 35, 0));
+        assert_eq!(resampled[2].datetime, date(15, 55, 0));
+
+        let resampled = resample(&bars, Timeframe::Hours(1), &NyseCalendar);
+        assert_eq!(resampled.len(), 2);
+        assert_eq!(resampled[1].datetime, date(15, 30, 0));
+    }
+
+    #[test]
+    fn it_resamples_daily_bars() {
+        let bars: Vec<Aggregate> = (4..=31)
+            .map(|day| {
+                priced(
+                    Eastern.with_ymd_and_hms(2021, 1, day, 0, 0, 0).unwrap(),
+                    day as i64,
+                )
+            })
+            .chain(std::iter::once(priced(
+                Eastern.with_ymd_and_hms(2021, 2, 1, 0, 0, 0).unwrap(),
+                32,
+            )))
+            .collect();
+        let weekly = resample(&bars, Timeframe::Week, &NyseCalendar);
+        // The weekend bars are skipped, and the week of the 18th starts on Tuesday after MLK day.
+        assert_eq!(weekly.len(), 5);
+        assert_eq!(weekly[0].close, Decimal::new(8, 0));
+        assert_eq!(
+            weekly[2].datetime,
+            Eastern.with_ymd_and_hms(2021, 1, 19, 0, 0, 0).unwrap()
+        );
+        assert_eq!(weekly[2].volume, Decimal::new(4, 0));
+
+        let monthly = resample(&bars, Timeframe::Month, &NyseCalendar);
+        assert_eq!(monthly.len(), 2);
+        assert_eq!(monthly[0].open, Decimal::new(4, 0));
+        assert_eq!(monthly[0].close, Decimal::new(29, 0));
+        assert_eq!(
+            monthly[1].datetime,
+            Eastern.with_ymd_and_hms(2021, 2, 1, 0, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn it_skips_extended_hours_in_daily_bars() {
+        let date = |hour, minute, second| {
+            Eastern
+                .with_ymd_and_hms(2021, 1, 5, hour, minute, second)
+                .unwrap()
+        };
+        let bars = vec![
+            priced(date(4, 0, 0), 1),
+            priced(date(9, 30, 0), 10),
+            priced(date(15, 59, 0), 11),
+            priced(date(16, 0, 0), 20),
+        ];
+        for timeframe in [Timeframe::Day, Timeframe::Week, Timeframe::Month].iter() {
+            let resampled = resample(&bars, *timeframe, &NyseCalendar);
+            assert_eq!(resampled.len(), 1);
+            assert_eq!(resampled[0].datetime, date(9, 30, 0));
+            assert_eq!(resampled[0].open, Decimal::new(10, 0));
+            assert_eq!(resampled[0].close, Decimal::new(11, 0));
+            assert_eq!(resampled[0].volume, Decimal::new(2, 0));
+        }
+    }
+
+    #[test]
+    fn it_resamples_onto_the_clock_grid() {
+        let date = |hour, minute, second| {
+            Eastern
+                .with_ymd_and_hms(2021, 1, 5, hour, minute, second)
+                .unwrap()
+        };
+        let bars = vec![
+            priced(date(3, 59, 0), 1),
+            priced(date(8, 45, 0), 2),
+            priced(date(9, 15, 0), 3),
+            priced(date(9, 30, 0), 10),
+            priced(date(10, 29, 0), 11),
+            priced(date(10, 30, 0), 12),
+            priced(date(16, 30, 0), 20),
+        ];
+        let hourly = resample_sessions(&bars, Duration::hours(1), &NyseCalendar, false);
+        assert_eq!(hourly.len(), 2);
+        assert_eq!(hourly[0].datetime, date(9, 30, 0));
+        assert_eq!(hourly[0].close, Decimal::new(11, 0));
+        assert_eq!(hourly[0].volume, Decimal::new(2, 0));
+        assert_eq!(hourly[1].datetime, date(10, 30, 0));
+
+        let hourly = resample_sessions(&bars, Duration::hours(1), &NyseCalendar, true);
+        let datetimes: Vec<DateTime<Tz>> = hourly.iter().map(|agg| agg.datetime).collect();
+        assert_eq!(
+            datetimes,
+            vec![
+                date(8, 0, 0),
+                date(9, 0, 0),
+                date(9, 30, 0),
+                date(10, 30, 0),
+                date(16, 0, 0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,27 @@
+//! Fixtures shared by the tests.
+use super::Aggregate;
+use chrono::{DateTime, TimeZone};
+use chrono_tz::{Tz, US::Eastern};
+use rust_decimal::Decimal;
+
+/// The open on 2021-01-05, the first full trading day of 2021.
+pub(crate) fn opening() -> DateTime<Tz> {
+    Eastern.with_ymd_and_hms(2021, 1, 5, 9, 30, 0).unwrap()
+}
+
+/// A bar at `datetime` with the given prices and a volume of one.
+pub(crate) fn bar(datetime: DateTime<Tz>, open: i64, high: i64, low: i64, close: i64) -> Aggregate {
+    Aggregate {
+        datetime,
+        open: Decimal::new(open, 0),
+        high: Decimal::new(high, 0),
+        low: Decimal::new(low, 0),
+        close: Decimal::new(close, 0),
+        volume: Decimal::ONE,
+    }
+}
+
+/// A bar at `datetime` that trades at `price` throughout, with a volume of one.
+pub(crate) fn flat_bar(datetime: DateTime<Tz>, price: i64) -> Aggregate {
+    bar(datetime, price, price, price, price)
+}
@@ -0,0 +1,402 @@
+use super::{Aggregate, MarketTimeExt};
+use crate::utils::exchange_calendar::ExchangeCalendar;
+use crate::{Resolution, Validation};
+use chrono::{DateTime, Duration, NaiveDate};
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataIssueKind {
+    /// The low is above the open or close, or the high is below them.
+    InvalidOhlc,
+    /// One of the prices is zero or negative.
+    NonPositivePrice,
+    /// The bar has no volume.
+    ZeroVolume,
+    /// More than one bar was returned for the same timestamp.
+    DuplicateTimestamp,
+    /// The bar falls outside of the NYSE regular trading session.
+    OutsideSession,
+    /// Too many bars are missing before this one.
+    Gap,
+    /// The close moved more than expected from the previous close.
+    Jump,
+}
+
+impl DataIssueKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::InvalidOhlc | Self::NonPositivePrice | Self::DuplicateTimestamp => {
+                Severity::Error
+            }
+            Self::ZeroVolume | Self::OutsideSession | Self::Gap | Self::Jump => Severity::Warning,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DataIssue {
+    pub ticker: String,
+    #[serde(with = "crate::utils::serde_tz")]
+    pub datetime: DateTime<Tz>,
+    pub kind: DataIssueKind,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl DataIssue {
+    pub fn new<T: ToString>(
+        ticker: &str,
+        datetime: DateTime<Tz>,
+        kind: DataIssueKind,
+        message: T,
+    ) -> Self {
+        Self {
+            ticker: ticker.to_string(),
+            datetime,
+            kind,
+            severity: kind.severity(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for DataIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} ({:?}, {:?}): {}",
+            self.ticker, self.datetime, self.kind, self.severity, self.message
+        )
+    }
+}
+
+/// Issues found while validating the data for a backtest.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ValidationReport {
+    pub issues: Vec<DataIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.issues
+            .iter()
+            .any(|issue| issue.severity == Severity::Error)
+    }
+
+    /// The number of issues of each kind, in the order the kinds were first found.
+    pub fn counts(&self) -> Vec<(DataIssueKind, usize)> {
+        let mut counts: Vec<(DataIssueKind, usize)> = Vec::new();
+        for issue in self.issues.iter() {
+            match counts.iter_mut().find(|(kind, _)| *kind == issue.kind) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((issue.kind, 1)),
+            }
+        }
+        counts
+    }
+}
+
+/// Summarizes the report by the number of issues of each kind, as it can hold an issue for every
+/// bar. The full list is written to the validation report of the backtest.
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} data issue(s)", self.issues.len())?;
+        for (kind, count) in self.counts() {
+            write!(f, "\n  {:?} ({:?}): {}", kind, kind.severity(), count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the configured checks on a single ticker's series. Duplicate timestamps can't be
+/// detected here, as they are collapsed when the series is built.
+pub(crate) fn validate(
+    ticker: &str,
+    series: &BTreeMap<DateTime<Tz>, Aggregate>,
+    resolution: Resolution,
+    calendar: &dyn ExchangeCalendar,
+    validation: &Validation,
+    extended_hours: bool,
+) -> Vec<DataIssue> {
+    let mut issues = Vec::new();
+    let mut previous: Option<&Aggregate> = None;
+    for (datetime, agg) in series.iter() {
+        let datetime = *datetime;
+        if validation.non_positive_prices
+            && [agg.open, agg.high, agg.low, agg.close]
+                .iter()
+                .any(|price| *price <= Decimal::ZERO)
+        {
+            issues.push(DataIssue::new(
+                ticker,
+                datetime,
+                DataIssueKind::NonPositivePrice,
+                format!(
+                    "open {}, high {}, low {}, close {}",
+                    agg.open, agg.high, agg.low, agg.close
+                ),
+            ))
+        }
+        if validation.ohlc
+            && (agg.low > agg.open.min(agg.close) || agg.high < agg.open.max(agg.close))
+        {
+            issues.push(DataIssue::new(
+                ticker,
+                datetime,
+                DataIssueKind::InvalidOhlc,
+                format!(
+                    "open {}, high {}, low {}, close {}",
+                    agg.open, agg.high, agg.low, agg.close
+                ),
+            ))
+        }
+        if validation.zero_volume && agg.volume.is_zero() {
+            issues.push(DataIssue::new(
+                ticker,
+                datetime,
+                DataIssueKind::ZeroVolume,
+                "No volume",
+            ))
+        }
+        if validation.sessions && !in_session(datetime, resolution, calendar, extended_hours) {
+            issues.push(DataIssue::new(
+                ticker,
+                datetime,
+                DataIssueKind::OutsideSession,
+                "Outside of trading hours",
+            ))
+        }
+        if let Some(prev) = previous {
+            if let Some(max_missing_bars) = validation.max_missing_bars {
+                let missing = missing_bars(prev.datetime, datetime, resolution, calendar);
+                if missing > max_missing_bars as i64 {
+                    issues.push(DataIssue::new(
+                        ticker,
+                        datetime,
+                        DataIssueKind::Gap,
+                        format!("{} bars missing since {}", missing, prev.datetime),
+                    ))
+                }
+            }
+            if let Some(max_jump) = validation.max_jump {
+                if !prev.close.is_zero() {
+                    let change = agg.close / prev.close - Decimal::ONE;
+                    if change.abs() > max_jump {
+                        issues.push(DataIssue::new(
+                            ticker,
+                            datetime,
+                            DataIssueKind::Jump,
+                            format!("Close moved from {} to {}", prev.close, agg.close),
+                        ))
+                    }
+                }
+            }
+        }
+        previous = Some(agg);
+    }
+    issues
+}
+
+fn in_session(
+    datetime: DateTime<Tz>,
+    resolution: Resolution,
+    calendar: &dyn ExchangeCalendar,
+    extended_hours: bool,
+) -> bool {
+    match resolution.duration() {
+        None => calendar.is_trading_day(local_date(datetime, calendar)),
+        Some(_) if extended_hours => {
+            let local = datetime.with_timezone(&calendar.timezone()).naive_local();
+            calendar
+                .extended_session(local.date())
+                .filter(|(opening, closing)| (*opening..*closing).contains(&local))
+                .is_some()
+        }
+        Some(_) => datetime.is_regular_hours_on(calendar),
+    }
+}
+
+fn local_date(datetime: DateTime<Tz>, calendar: &dyn ExchangeCalendar) -> NaiveDate {
+    datetime.with_timezone(&calendar.timezone()).date_naive()
+}
+
+/// The number of bars expected strictly between two bars.
+fn missing_bars(
+    previous: DateTime<Tz>,
+    next: DateTime<Tz>,
+    resolution: Resolution,
+    calendar: &dyn ExchangeCalendar,
+) -> i64 {
+    let trading_date = |datetime| {
+        calendar
+            .trading_date(datetime)
+            .unwrap_or_else(|| local_date(datetime, calendar))
+    };
+    let previous_date = trading_date(previous);
+    let next_date = trading_date(next);
+    let skipped_dates = previous_date
+        .iter_days()
+        .skip(1)
+        .take_while(|date| *date < next_date)
+        .filter(|date| calendar.is_trading_day(*date));
+    let step = match resolution.duration() {
+        None => return skipped_dates.count() as i64,
+        Some(step) => step.num_milliseconds(),
+    };
+    let steps = |duration: Duration| duration.num_milliseconds() / step;
+    if previous_date == next_date {
+        return steps(next - previous) - 1;
+    }
+    let before_close = calendar
+        .closing(previous_date)
+        .map(|closing| steps(closing - previous) - 1)
+        .unwrap_or(0);
+    let after_open = calendar
+        .opening(next_date)
+        .map(|opening| steps(next - opening))
+        .unwrap_or(0);
+    let skipped: i64 = skipped_dates
+        .filter_map(|date| Some(steps(calendar.closing(date)? - calendar.opening(date)?)))
+        .sum();
+    before_close.max(0) + after_open.max(0) + skipped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::testing::bar;
+    use crate::utils::nyse_calendar::NyseCalendar;
+    use chrono::{TimeZone, Timelike};
+    use chrono_tz::US::Eastern;
+
+    #[test]
+    fn it_flags_bad_bars() {
+        let mut bars = vec![
+            bar(
+                Eastern.with_ymd_and_hms(2021, 1, 5, 9, 30, 0).unwrap(),
+                10,
+                11,
+                9,
+                10,
+            ),
+            bar(
+                Eastern.with_ymd_and_hms(2021, 1, 5, 9, 31, 0).unwrap(),
+                10,
+                9,
+                9,
+                10,
+            ),
+            bar(
+                Eastern.with_ymd_and_hms(2021, 1, 5, 9, 35, 0).unwrap(),
+                10,
+                11,
+                0,
+                10,
+            ),
+            bar(
+                Eastern.with_ymd_and_hms(2021, 1, 5, 9, 36, 0).unwrap(),
+                20,
+                20,
+                20,
+                20,
+            ),
+            bar(
+                Eastern.with_ymd_and_hms(2021, 1, 5, 16, 30, 0).unwrap(),
+                20,
+                20,
+                20,
+                20,
+            ),
+            bar(
+                Eastern.with_ymd_and_hms(2021, 1, 6, 9, 30, 0).unwrap(),
+                20,
+                20,
+                20,
+                20,
+            ),
+        ];
+        bars[5].volume = Decimal::ZERO;
+        let series = bars.into_iter().map(|agg| (agg.datetime, agg)).collect();
+        let validation = Validation {
+            max_missing_bars: Some(2),
+            zero_volume: true,
+            ..Validation::default()
+        };
+        let issues = validate(
+            "AAPL",
+            &series,
+            Resolution::Minute,
+            &NyseCalendar,
+            &validation,
+            false,
+        );
+        let kinds: Vec<(u32, u32, DataIssueKind)> = issues
+            .iter()
+            .map(|issue| (issue.datetime.hour(), issue.datetime.minute(), issue.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (9, 31, DataIssueKind::InvalidOhlc),
+                (9, 35, DataIssueKind::NonPositivePrice),
+                (9, 35, DataIssueKind::Gap),
+                (9, 36, DataIssueKind::Jump),
+                (16, 30, DataIssueKind::OutsideSession),
+                (16, 30, DataIssueKind::Gap),
+                (9, 30, DataIssueKind::ZeroVolume),
+            ]
+        );
+        let report = ValidationReport { issues };
+        assert!(report.has_errors());
+        assert_eq!(report.counts().len(), 6);
+        assert_eq!(report.counts()[2], (DataIssueKind::Gap, 2));
+    }
+
+    #[test]
+    fn it_counts_missing_bars_across_sessions() {
+        let friday = |hour, minute, second| {
+            Eastern
+                .with_ymd_and_hms(2021, 1, 8, hour, minute, second)
+                .unwrap()
+        };
+        let tuesday = |hour, minute, second| {
+            Eastern
+                .with_ymd_and_hms(2021, 1, 12, hour, minute, second)
+                .unwrap()
+        };
+        assert_eq!(
+            missing_bars(
+                friday(15, 58, 0),
+                tuesday(9, 31, 0),
+                Resolution::Minute,
+                &NyseCalendar
+            ),
+            1 + 1 + 390
+        );
+        assert_eq!(
+            missing_bars(
+                friday(0, 0, 0),
+                tuesday(0, 0, 0),
+                Resolution::Day,
+                &NyseCalendar
+            ),
+            1
+        );
+    }
+}
@@ -1,5 +1,31 @@
+use rust_decimal::prelude::*;
+
 pub trait Slippage: Send + Sync {
+    /// The fraction of the price that a fill moves against the order, given the order's share of
+    /// the volume traded.
     fn slippage(&self, volume_share: f64) -> f64;
+
+    /// The largest share of a bar's volume an order can fill in that bar, if fills are capped.
+    fn max_volume_share(&self) -> Option<f64> {
+        None
+    }
+
+    /// The price an order for `shares` fills at when the market price is `price` and `volume`
+    /// shares traded in the last bar. Without any volume, the order is taken to be all of it.
+    fn fill_price(&self, price: Decimal, shares: Decimal, volume: Decimal) -> Decimal {
+        let volume_share = if volume.is_zero() {
+            Decimal::ONE
+        } else {
+            (shares.abs() / volume).min(Decimal::ONE)
+        };
+        let slippage = self.slippage(volume_share.to_f64().unwrap_or(1.0));
+        let slippage = Decimal::from_f64(slippage).unwrap_or_default();
+        if shares.is_sign_positive() {
+            price * (Decimal::ONE + slippage)
+        } else {
+            price * (Decimal::ONE - slippage)
+        }
+    }
 }
 
 pub struct NoSlippage;
@@ -10,11 +36,14 @@ impl Slippage for NoSlippage {
     }
 }
 
+/// Moves every fill against the order by a fixed fraction of the price, e.g. `0.001` for 10 basis
+/// points.
 pub struct FixedSlippage {
     amount: f64,
 }
 
 impl FixedSlippage {
+    /// Creates a model that slips by `amount`, given as a fraction of the price.
     pub fn new(amount: f64) -> Self {
         Self { amount }
     }
@@ -39,11 +68,16 @@ impl VolumeShareSlippage {
 }
 impl Slippage for VolumeShareSlippage {
     fn slippage(&self, volume_share: f64) -> f64 {
-        if let Some(_max_volume) = self.max_volume {
-            todo!("Implement max volume for VolumeShareSlippage")
-        } else {
-            self.price_impact * volume_share.powi(2)
-        }
+        // Fills never take more than `max_volume` of a bar
+        let volume_share = match self.max_volume {
+            Some(max_volume) => volume_share.min(max_volume),
+            None => volume_share,
+        };
+        self.price_impact * volume_share.powi(2)
+    }
+
+    fn max_volume_share(&self) -> Option<f64> {
+        self.max_volume
     }
 }
 
@@ -60,5 +94,32 @@ mod test {
         assert_eq!(no_slippage.slippage(0.5), 0.0);
         assert_eq!(fixed_slippage.slippage(0.5), 1.0);
         assert_eq!(volume_share_slippage.slippage(0.5), 0.5);
+
+        let capped_slippage = VolumeShareSlippage::new(2.0, Some(0.25));
+        assert_eq!(capped_slippage.slippage(0.5), 0.125);
+        assert_eq!(capped_slippage.max_volume_share(), Some(0.25));
+        assert_eq!(volume_share_slippage.max_volume_share(), None);
+    }
+
+    #[test]
+    fn it_moves_the_fill_price_against_the_order() {
+        let price = Decimal::new(100, 0);
+        let slippage = VolumeShareSlippage::new(0.4, None);
+        assert_eq!(
+            slippage.fill_price(price, Decimal::new(50, 0), Decimal::new(100, 0)),
+            Decimal::new(110, 0)
+        );
+        assert_eq!(
+            slippage.fill_price(price, Decimal::new(-50, 0), Decimal::new(100, 0)),
+            Decimal::new(90, 0)
+        );
+        assert_eq!(
+            slippage.fill_price(price, Decimal::new(50, 0), Decimal::ZERO),
+            Decimal::new(140, 0)
+        );
+        assert_eq!(
+            NoSlippage.fill_price(price, Decimal::new(50, 0), Decimal::ZERO),
+            price
+        );
     }
 }
@@ -13,22 +13,58 @@ mod utils;
 
 pub use brokerage::{
     actor::Event,
+    fill::IntrabarPath,
     handle::Brokerage,
-    order::{Order, OrderStatus, OrderType},
+    order::{Order, OrderStatus, OrderType, TimeInForce},
+};
+#[cfg(feature = "parquet")]
+pub use data::parquet::ParquetProvider;
+#[cfg(feature = "polygon")]
+pub use data::polygon::PolygonProvider;
+pub use data::{
+    cache::{FileCache, FileDataCache},
+    corporate_actions::{CorporateAction, CorporateActions, CorporateActionsExt},
+    csv::CsvProvider,
+    history::History,
+    provider::DataProvider,
+    resample::Timeframe,
+    Aggregate,
+};
+pub use markets::{
+    clock::MarketState,
+    handle::{Market, TickerSnapshot},
+};
+pub use options::{
+    BarTimestamp, ClockMode, DownloadPolicy, MissingBarPolicy, Options, Resolution, Validation,
 };
-pub use data::Aggregate;
-pub use markets::{clock::MarketState, handle::Market};
-pub use options::{Options, Resolution};
 pub use simulator::Simulator;
 pub use strategy::Strategy;
+pub use utils::{
+    cme_calendar::CmeCalendar,
+    exchange_calendar::{AlwaysOpenCalendar, CustomCalendar, Exchange, ExchangeCalendar},
+    lse_calendar::LseCalendar,
+    nyse_calendar::NyseCalendar,
+    tsx_calendar::TsxCalendar,
+};
 
 pub mod prelude {
-    pub use crate::data::MarketTimeExt;
+    #[cfg(feature = "parquet")]
+    pub use crate::data::parquet::ParquetProvider;
+    #[cfg(feature = "polygon")]
+    pub use crate::data::polygon::PolygonProvider;
+    pub use crate::data::{
+        cache::FileCache, corporate_actions::CorporateActionsExt, csv::CsvProvider,
+        history::History, provider::DataProvider, resample::Timeframe, MarketTimeExt,
+    };
     pub use crate::{
         brokerage::{handle::Brokerage, order::Order},
         markets::handle::Market,
-        options::{Options, Resolution},
+        options::{
+            BarTimestamp, ClockMode, DownloadPolicy, MissingBarPolicy, Options, Resolution,
+            Validation,
+        },
         simulator::Simulator,
         strategy::Strategy,
+        utils::exchange_calendar::{Exchange, ExchangeCalendar},
     };
 }
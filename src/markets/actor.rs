@@ -1,39 +1,48 @@
+use crate::data::{corporate_actions::CorporateAction, history::History, resample::Timeframe};
 use crate::markets::clock::{Clock, MarketState};
 use crate::markets::data_manager::DataManager;
 use crate::markets::handle::*;
 use crate::utils::progress::progress;
 use crate::Aggregate;
-use crate::Options;
+use crate::{BarTimestamp, ClockMode, Options, Resolution};
 use chrono::prelude::*;
+use chrono::Duration;
 use chrono_tz::Tz;
 use indicatif::ProgressBar;
 use rust_decimal::Decimal;
+use std::collections::HashMap;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 use tokio::sync::oneshot::Sender as OneshotSender;
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
 pub(crate) struct MarketActor {
     requests: UnboundedReceiver<(OneshotSender<MarketResponse>, MarketRequest)>,
+    data_options: Options,
     data_manager: DataManager,
     clock: Clock,
     progress: ProgressBar,
 }
 
 impl MarketActor {
-    pub fn spawn(data_options: Options) -> Market {
-        let clock = Clock::new(
+    pub fn spawn(data_options: Options, data_manager: DataManager) -> Market {
+        let mut clock = Clock::new(
             data_options.start,
             data_options.end,
             data_options.warmup,
             data_options.resolution,
+            data_options.exchange.calendar(),
+            data_options.extended_hours,
         );
+        if let ClockMode::Sparse = data_options.clock_mode {
+            clock = clock.sparse(data_manager.timestamps());
+        }
         let progress = progress(clock.simulation_periods() as u64, "Simulating");
-        let data_manager = DataManager::new(data_options);
         let (tx, rx) = unbounded_channel();
         let handle = Market::new(tx);
 
         let actor = Self {
             requests: rx,
+            data_options,
             data_manager,
             clock,
             progress,
@@ -43,8 +52,6 @@ impl MarketActor {
     }
 
     async fn run_forever(mut self) {
-        self.data_manager.download_data().await;
-        self.progress.reset();
         while let Some((tx, request)) = self.requests.recv().await {
             trace!("Received request: {:?}", request);
             let response = self.handle_message(request);
@@ -59,19 +66,51 @@ impl MarketActor {
                 let data = self.get_data(&ticker, start, end);
                 MarketResponse::Data(data)
             }
-            MarketRequest::GetOpen { ticker } => MarketResponse::MaybePrice(self.get_open(&ticker)),
-            MarketRequest::GetCurrent { ticker } => {
-                MarketResponse::MaybePrice(self.get_current_price(&ticker))
+            MarketRequest::ResampledData {
+                ticker,
+                start,
+                end,
+                timeframe,
+            } => {
+                let data = self.get_resampled_data(&ticker, start, end, timeframe);
+                MarketResponse::Data(data)
             }
+            MarketRequest::History {
+                ticker,
+                n_bars,
+                timeframe,
+            } => MarketResponse::Data(self.history(&ticker, n_bars, timeframe)),
+            MarketRequest::Histories {
+                tickers,
+                n_bars,
+                timeframe,
+            } => MarketResponse::History(self.histories(tickers, n_bars, timeframe)),
+            MarketRequest::GetOpen { ticker } => MarketResponse::MaybePrice(self.get_open(&ticker)),
             MarketRequest::GetLast { ticker } => {
                 MarketResponse::MaybePrice(self.get_last_price(&ticker))
             }
+            MarketRequest::MarkPrices { tickers } => {
+                MarketResponse::Prices(self.mark_prices(tickers))
+            }
+            MarketRequest::Snapshot { tickers } => MarketResponse::Snapshot(self.snapshot(tickers)),
+            MarketRequest::PriceAge { ticker } => {
+                MarketResponse::MaybeDuration(self.get_price_age(&ticker))
+            }
+            MarketRequest::Bars { tickers } => MarketResponse::Bars(self.get_current_bars(tickers)),
+            MarketRequest::CorporateActions => {
+                MarketResponse::CorporateActions(self.get_corporate_actions())
+            }
             MarketRequest::Datetime => MarketResponse::Datetime(self.datetime()),
             MarketRequest::PreviousDatetime => MarketResponse::Datetime(self.previous_datetime()),
             MarketRequest::NextDatetime => MarketResponse::Datetime(self.next_datetime()),
             MarketRequest::State => MarketResponse::State(self.state()),
             MarketRequest::IsDone => MarketResponse::Bool(self.is_done()),
+            MarketRequest::IsExtendedHours => MarketResponse::Bool(self.is_extended_hours()),
             MarketRequest::IsOpen => MarketResponse::Bool(self.is_open()),
+            MarketRequest::Schedule { datetime } => {
+                self.schedule(datetime);
+                MarketResponse::Success
+            }
             MarketRequest::Tick => {
                 self.tick();
                 MarketResponse::Success
@@ -82,26 +121,89 @@ impl MarketActor {
     #[tracing::instrument(skip(self))]
     fn get_open(&self, ticker: &str) -> Option<Decimal> {
         trace!(ticker, "Get open");
-        let datetime = self.datetime();
-        let data = self.get_data(ticker, datetime, datetime);
-        data.map(|x| x.first().unwrap().open)
+        self.data_manager
+            .get_bar(ticker, self.datetime())
+            .map(|x| x.open)
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn get_current_bars(&self, tickers: Vec<String>) -> HashMap<String, Aggregate> {
+        trace!(?tickers, "Get current bars");
+        // Intraday clocks close at the closing time, after the last bar of the session
+        let datetime = match (self.state(), self.data_options.resolution.duration()) {
+            (MarketState::Closing, Some(step)) => self.datetime() - step,
+            _ => self.datetime(),
+        };
+        tickers
+            .into_iter()
+            .filter_map(|ticker| {
+                let bar = self.data_manager.get_bar(&ticker, datetime)?;
+                Some((ticker, bar))
+            })
+            .collect()
     }
 
     #[tracing::instrument(skip(self))]
     fn get_current_price(&self, ticker: &str) -> Option<Decimal> {
         trace!(ticker, "Get current price");
-        let datetime = self.datetime();
-        self.data_manager
-            .get_last_before(ticker, datetime)
-            .map(|x| x.close)
+        let bar = self.data_manager.get_bar(ticker, self.datetime())?;
+        if self.data_options.prevent_look_ahead && self.last_known_bar() < self.datetime() {
+            // The bar stamped at the current time has only just opened
+            Some(bar.open)
+        } else {
+            Some(bar.close)
+        }
     }
 
     #[tracing::instrument(skip(self))]
     pub fn get_last_price(&self, ticker: &str) -> Option<Decimal> {
         trace!(ticker, "Get last price");
-        let datetime = self.previous_datetime();
-        let data = self.get_data(ticker, datetime, datetime);
-        data.map(|x| x.last().unwrap().close)
+        self.data_manager
+            .get_bar(ticker, self.previous_datetime())
+            .map(|x| x.close)
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn get_price_age(&self, ticker: &str) -> Option<Duration> {
+        trace!(ticker, "Get price age");
+        self.data_manager.get_price_age(ticker, self.datetime())
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn mark_prices(&self, tickers: Vec<String>) -> HashMap<String, Decimal> {
+        trace!(?tickers, "Get mark prices");
+        tickers
+            .into_iter()
+            .filter_map(|ticker| {
+                // Without a current bar, the last bar before now has closed
+                let price = self.get_current_price(&ticker).or_else(|| {
+                    self.data_manager
+                        .get_last_before(&ticker, self.datetime())
+                        .map(|bar| bar.close)
+                })?;
+                Some((ticker, price))
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn snapshot(&self, tickers: Vec<String>) -> HashMap<String, TickerSnapshot> {
+        trace!(?tickers, "Get snapshot");
+        // The bar at the current time has only just opened, unless bars are stamped at their close
+        let known = if self.data_options.prevent_look_ahead {
+            self.last_known_bar()
+        } else {
+            self.datetime()
+        };
+        tickers
+            .into_iter()
+            .filter_map(|ticker| {
+                let price = self.get_current_price(&ticker)?;
+                let age = self.get_price_age(&ticker)?;
+                let bar = self.data_manager.get_bar(&ticker, known);
+                Some((ticker, TickerSnapshot { bar, price, age }))
+            })
+            .collect()
     }
 
     #[tracing::instrument(skip(self))]
@@ -112,9 +214,91 @@ impl MarketActor {
         end: DateTime<Tz>,
     ) -> Option<Vec<Aggregate>> {
         trace!(ticker, %start, %end, "Get data");
+        let end = self.clamp_end(ticker, end);
         self.data_manager.get_data(ticker, start, end)
     }
 
+    #[tracing::instrument(skip(self))]
+    fn get_resampled_data(
+        &self,
+        ticker: &str,
+        start: DateTime<Tz>,
+        end: DateTime<Tz>,
+        timeframe: Timeframe,
+    ) -> Option<Vec<Aggregate>> {
+        trace!(ticker, %start, %end, ?timeframe, "Get resampled data");
+        let end = self.clamp_end(ticker, end);
+        self.data_manager
+            .get_resampled_data(ticker, start, end, timeframe)
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn history(&self, ticker: &str, n_bars: usize, timeframe: Timeframe) -> Option<Vec<Aggregate>> {
+        trace!(ticker, n_bars, ?timeframe, "Get history");
+        let end = if self.data_options.prevent_look_ahead {
+            self.last_known_bar()
+        } else {
+            self.datetime()
+        };
+        self.data_manager.history(ticker, end, n_bars, timeframe)
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn histories(&self, tickers: Vec<String>, n_bars: usize, timeframe: Timeframe) -> History {
+        let histories = tickers
+            .into_iter()
+            .filter_map(|ticker| {
+                let history = self.history(&ticker, n_bars, timeframe)?;
+                Some((ticker, history))
+            })
+            .collect();
+        History::align(histories, n_bars)
+    }
+
+    /// The timestamp of the last bar that has closed by the current time.
+    fn last_known_bar(&self) -> DateTime<Tz> {
+        let closed = matches!(
+            self.state(),
+            MarketState::Closing | MarketState::AfterHours | MarketState::Closed
+        );
+        match (
+            self.data_options.bar_timestamp,
+            self.data_options.resolution,
+        ) {
+            (BarTimestamp::Close, _) => self.datetime(),
+            // The clock stays at the open all day, and the daily bar closes with the session
+            (BarTimestamp::Open, Resolution::Day) if closed => self.datetime(),
+            (BarTimestamp::Open, resolution) => {
+                let step = resolution.duration().unwrap_or_else(|| Duration::days(1));
+                self.datetime() - step
+            }
+        }
+    }
+
+    /// Clamps the end of a query to the bars that would have been known at the current time.
+    fn clamp_end(&self, ticker: &str, end: DateTime<Tz>) -> DateTime<Tz> {
+        if !self.data_options.prevent_look_ahead {
+            return end;
+        }
+        let known = self.last_known_bar();
+        if end <= known {
+            return end;
+        }
+        if self
+            .data_manager
+            .has_data(ticker, known + Duration::nanoseconds(1), end)
+        {
+            warn!(ticker, %end, %known, "Clamped query to prevent look-ahead");
+        }
+        known
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn get_corporate_actions(&mut self) -> Vec<CorporateAction> {
+        let date = self.datetime().date_naive();
+        self.data_manager.get_corporate_actions(date)
+    }
+
     #[tracing::instrument(skip(self))]
     fn datetime(&self) -> DateTime<Tz> {
         self.clock.datetime()
@@ -140,6 +324,11 @@ impl MarketActor {
         self.clock.is_open()
     }
 
+    #[tracing::instrument(skip(self))]
+    fn is_extended_hours(&self) -> bool {
+        self.clock.is_extended_hours()
+    }
+
     #[tracing::instrument(skip(self))]
     fn previous_datetime(&self) -> DateTime<Tz> {
         self.clock.previous_datetime()
@@ -150,9 +339,152 @@ impl MarketActor {
         self.clock.next_datetime()
     }
 
+    #[tracing::instrument(skip(self))]
+    fn schedule(&mut self, datetime: DateTime<Tz>) {
+        trace!(%datetime, "Schedule");
+        self.clock.schedule(datetime)
+    }
+
     #[tracing::instrument(skip(self))]
     fn tick(&mut self) {
         self.clock.tick();
         self.progress.inc(1);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::{
+        error::Error,
+        provider::DataProvider,
+        testing::{bar, opening},
+        MarketData,
+    };
+    use async_trait::async_trait;
+    use chrono_tz::US::Eastern;
+
+    /// Returns minute bars from 09:30 to 09:35 whose close is one above their open.
+    struct MinuteProvider;
+
+    #[async_trait]
+    impl DataProvider for MinuteProvider {
+        async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+            let bars = (0..6)
+                .map(|minute| {
+                    let datetime = opening() + Duration::minutes(minute);
+                    bar(datetime, minute, minute + 1, minute, minute + 1)
+                })
+                .collect();
+            let mut data = MarketData::new();
+            data.insert(meta.tickers[0].clone(), bars);
+            Ok(data)
+        }
+    }
+
+    async fn spawn_market(prevent_look_ahead: bool) -> Market {
+        let options = Options::new(
+            vec!["AAPL".to_string()],
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+        )
+        .set_resolution(Resolution::Minute)
+        .set_prevent_look_ahead(prevent_look_ahead);
+        let mut data_manager = DataManager::new(options.clone(), Box::new(MinuteProvider));
+        data_manager.download_data().await.unwrap();
+        let market = MarketActor::spawn(options, data_manager);
+        // Step to 09:32
+        for _ in 0..4 {
+            market.tick().await;
+        }
+        market
+    }
+
+    #[tokio::test]
+    async fn it_prevents_look_ahead() {
+        let opening = Eastern.with_ymd_and_hms(2021, 1, 5, 9, 30, 0).unwrap();
+        let end = opening + Duration::minutes(5);
+
+        let market = spawn_market(true).await;
+        assert_eq!(market.datetime().await, opening + Duration::minutes(2));
+        let data = market.get_data("AAPL", opening, end).await.unwrap();
+        assert_eq!(data.len(), 2);
+        assert_eq!(
+            market.get_last_price("AAPL").await,
+            Some(Decimal::new(2, 0))
+        );
+        let snapshot = market.snapshot(&["AAPL", "MSFT"]).await;
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot["AAPL"].price, Decimal::new(2, 0));
+        assert_eq!(snapshot["AAPL"].age, Duration::zero());
+        assert_eq!(
+            snapshot["AAPL"].bar.as_ref().unwrap().datetime,
+            opening + Duration::minutes(1)
+        );
+
+        let market = spawn_market(false).await;
+        let data = market.get_data("AAPL", opening, end).await.unwrap();
+        assert_eq!(data.len(), 6);
+        assert_eq!(
+            market.snapshot(&["AAPL"]).await["AAPL"].price,
+            Decimal::new(3, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn it_snapshots_tickers() {
+        let opening = Eastern.with_ymd_and_hms(2021, 1, 5, 9, 30, 0).unwrap();
+
+        let market = spawn_market(false).await;
+        let snapshot = market.snapshot(&["AAPL"]).await;
+        let bar = snapshot["AAPL"].bar.as_ref().unwrap();
+        assert_eq!(bar.datetime, opening + Duration::minutes(2));
+        assert_eq!(snapshot["AAPL"].price, bar.close);
+        assert_eq!(snapshot["AAPL"].age, Duration::zero());
+
+        // Step to 09:38, three minutes after the last bar
+        let market = spawn_market(true).await;
+        for _ in 0..6 {
+            market.tick().await;
+        }
+        let snapshot = market.snapshot(&["AAPL"]).await;
+        let bar = snapshot["AAPL"].bar.as_ref().unwrap();
+        assert_eq!(bar.datetime, opening + Duration::minutes(7));
+        assert_eq!(bar.close, Decimal::new(6, 0));
+        assert_eq!(bar.volume, Decimal::ZERO);
+        assert_eq!(snapshot["AAPL"].price, Decimal::new(6, 0));
+        assert_eq!(snapshot["AAPL"].age, Duration::minutes(3));
+    }
+
+    /// Returns a single daily bar on 2021-01-05 that opens at 1 and closes at 2.
+    struct DailyProvider;
+
+    #[async_trait]
+    impl DataProvider for DailyProvider {
+        async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+            let mut data = MarketData::new();
+            data.insert(meta.tickers[0].clone(), vec![bar(opening(), 1, 2, 1, 2)]);
+            Ok(data)
+        }
+    }
+
+    #[tokio::test]
+    async fn it_knows_the_daily_close_at_closing() {
+        let options = Options::new(
+            vec!["AAPL".to_string()],
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+        )
+        .set_prevent_look_ahead(true);
+        let mut data_manager = DataManager::new(options.clone(), Box::new(DailyProvider));
+        data_manager.download_data().await.unwrap();
+        let market = MarketActor::spawn(options, data_manager);
+        market.tick().await;
+        assert_eq!(market.state().await, MarketState::Opening);
+        assert_eq!(market.snapshot(&["AAPL"]).await["AAPL"].price, Decimal::ONE);
+        market.tick().await;
+        market.tick().await;
+        assert_eq!(market.state().await, MarketState::Closing);
+        assert_eq!(market.snapshot(&["AAPL"]).await["AAPL"].price, Decimal::TWO);
+    }
+}
@@ -1,120 +1,203 @@
-use crate::utils::nyse_calendar::NyseCalendar;
+use crate::utils::exchange_calendar::ExchangeCalendar;
 use crate::Resolution;
-use bdays::{HolidayCalendar, HolidayCalendarCache};
-use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone};
-use chrono_tz::{Tz, US::Eastern};
-
-lazy_static! {
-    static ref OPENING_TIME: NaiveTime = NaiveTime::from_hms(9, 30, 0);
-    static ref CLOSING_TIME: NaiveTime = NaiveTime::from_hms(16, 0, 0);
-}
+use chrono::{DateTime, Duration, NaiveDate};
+use chrono_tz::Tz;
+use std::collections::BTreeSet;
+use std::ops::Bound::{Excluded, Unbounded};
+use std::sync::Arc;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum MarketState {
+    /// Stepping through the regular sessions before the start of the backtest, so that
+    /// strategies can build up history. Orders can't be placed.
+    Warmup,
     PreOpen,
+    /// Pre-market trading, only simulated when extended hours are enabled
+    PreMarket,
     Opening,
     Open,
     Closing,
+    /// After-hours trading, only simulated when extended hours are enabled
+    AfterHours,
     Closed,
 }
 
-impl MarketState {
-    fn next(&self) -> Self {
-        match self {
-            Self::PreOpen => Self::Opening,
-            Self::Opening => Self::Open,
-            Self::Open => Self::Closing,
-            Self::Closing => Self::Closed,
-            Self::Closed => Self::PreOpen,
-        }
-    }
-}
-
 #[derive(Clone)]
 struct ClockOptions {
+    /// The first trading day after the warmup
+    start: NaiveDate,
     end: NaiveDate,
     resolution: Resolution,
+    extended_hours: bool,
+    /// Only stop at `events` and session boundaries rather than at every step.
+    sparse: bool,
 }
 
 pub struct Clock {
     datetime: DateTime<Tz>,
+    /// The trading day of the current session
+    date: NaiveDate,
     market_state: MarketState,
-    calendar: HolidayCalendarCache<NaiveDate>,
+    calendar: Arc<dyn ExchangeCalendar>,
+    /// Times the clock must stop at, in addition to its steps
+    events: BTreeSet<DateTime<Tz>>,
     options: ClockOptions,
 }
 
 impl Clock {
     pub fn new(
-        mut start: NaiveDate,
+        start: NaiveDate,
         end: NaiveDate,
         warmup: Duration,
         resolution: Resolution,
+        calendar: Arc<dyn ExchangeCalendar>,
+        extended_hours: bool,
     ) -> Self {
-        let calendar = HolidayCalendarCache::new(NyseCalendar, start, end);
-        if !calendar.is_bday(start) {
-            start = calendar.advance_bdays(start, 1);
+        if let Some(step) = resolution.duration() {
+            assert!(step > Duration::zero(), "Resolution must be positive");
+        }
+        let mut start = start;
+        if !calendar.is_trading_day(start) {
+            // Roll forward to a trading day and then advance one more, as `bdays::advance_bdays`
+            // did before calendars were pluggable.
+            start = calendar.next_trading_day(calendar.next_trading_day(start.pred_opt().unwrap()));
+        }
+        let mut date = (start.and_hms_opt(0, 0, 0).unwrap() - warmup).date();
+        if !calendar.is_trading_day(date) {
+            date = calendar.next_trading_day(date);
         }
-        let datetime = Eastern
-            .from_local_datetime(&start.and_time(*OPENING_TIME))
-            .unwrap()
-            + warmup;
-        let options = ClockOptions { end, resolution };
-        Self {
-            datetime,
-            market_state: MarketState::PreOpen,
+        let options = ClockOptions {
+            start,
+            end,
+            resolution,
+            // There is only one tick per day for daily data, so there are no extended hours to
+            // step through.
+            extended_hours: extended_hours && resolution.duration().is_some(),
+            sparse: false,
+        };
+        let mut clock = Self {
+            datetime: calendar.opening(date).unwrap(),
+            date,
+            market_state: MarketState::Warmup,
             calendar,
+            events: BTreeSet::new(),
             options,
+        };
+        if date >= start {
+            clock.date = start;
+            clock.datetime = clock.start_of_day(start);
+            clock.market_state = MarketState::PreOpen;
+        }
+        clock
+    }
+
+    /// Makes the clock skip straight to the next of `timestamps` during a session instead of
+    /// ticking at every step. The session boundaries of each day are still ticked.
+    pub fn sparse(mut self, timestamps: BTreeSet<DateTime<Tz>>) -> Self {
+        self.events.extend(timestamps);
+        self.options.sparse = true;
+        self
+    }
+
+    /// Makes sure the clock stops at `datetime`, if it is during a simulated session and hasn't
+    /// passed yet.
+    pub fn schedule(&mut self, datetime: DateTime<Tz>) {
+        let local = datetime
+            .with_timezone(&self.calendar.timezone())
+            .naive_local();
+        let date = local.date();
+        let in_session = [date, date.succ_opt().unwrap(), date.pred_opt().unwrap()]
+            .iter()
+            .any(|date| {
+                let session = if self.options.extended_hours {
+                    self.calendar.extended_session(*date)
+                } else {
+                    self.calendar.session(*date)
+                };
+                session
+                    .filter(|(opening, closing)| (*opening..=*closing).contains(&local))
+                    .is_some()
+            });
+        if in_session && datetime > self.datetime {
+            self.events.insert(datetime);
         }
     }
 
     pub fn simulation_periods(&self) -> i32 {
-        let days = self
-            .calendar
-            .bdays(self.datetime.date().naive_local(), self.options.end);
-        match self.options.resolution {
-            Resolution::Day => days * 5,
-            Resolution::Minute => days * 395,
+        // Each session has a tick per step plus the state changes
+        let mut periods = 0;
+        let mut date = self.date;
+        while date < self.options.end {
+            if date < self.options.start {
+                // Warmup only ticks through the regular session
+                if self.calendar.is_trading_day(date) {
+                    periods += match self.options.resolution.duration() {
+                        Some(step) => self.steps(self.opening(date), self.closing(date), step),
+                        None => 0,
+                    } as i32
+                        + 1;
+                }
+            } else if self.calendar.is_trading_day(date) {
+                periods += match self.options.resolution.duration() {
+                    Some(step) => {
+                        let regular = self.steps(self.opening(date), self.closing(date), step);
+                        if self.options.extended_hours {
+                            let pre_market =
+                                self.steps(self.start_of_day(date), self.opening(date), step);
+                            let after_hours =
+                                self.steps(self.closing(date), self.end_of_day(date), step);
+                            regular + pre_market + after_hours + 2
+                        } else {
+                            regular
+                        }
+                    }
+                    None => 0,
+                } as i32
+                    + 5;
+            }
+            date = date.succ_opt().unwrap();
         }
+        periods
     }
 
     pub fn is_done(&self) -> bool {
-        (self.datetime.date().naive_local() >= self.options.end)
-            && self.market_state == MarketState::Closed
+        (self.date >= self.options.end) && self.market_state == MarketState::Closed
     }
 
     pub fn is_start_of_day(&self) -> bool {
-        match self.options.resolution {
-            Resolution::Minute => self.datetime.time() == *OPENING_TIME,
-            // Since there's only one tick per day, it's always the end of the day
-            Resolution::Day => true,
+        match self.options.resolution.duration() {
+            Some(_) => self.datetime == self.start_of_day(self.date),
+            // Since there's only one tick per day, it's always the start of the day
+            None => true,
         }
     }
 
     pub fn is_end_of_day(&self) -> bool {
-        // TODO: Fix for early closing time
-        match self.options.resolution {
-            Resolution::Minute => self.datetime.time() == *CLOSING_TIME,
+        match self.options.resolution.duration() {
+            Some(_) => self.datetime == self.end_of_day(self.date),
             // Since there's only one tick per day, it's always the end of the day
-            Resolution::Day => true,
+            None => true,
         }
     }
 
     pub fn previous_datetime(&self) -> DateTime<Tz> {
         if self.is_start_of_day() {
-            Eastern
-                .from_local_datetime(
-                    &self
-                        .calendar
-                        .advance_bdays(self.datetime.date().naive_local(), -1)
-                        // TODO: Fix for early closing time
-                        .and_time(*CLOSING_TIME),
-                )
-                .unwrap()
+            self.end_of_day(self.calendar.previous_trading_day(self.date))
         } else {
-            match self.options.resolution {
-                Resolution::Minute => self.datetime - Duration::minutes(1),
-                Resolution::Day => unreachable!(),
-            }
+            // We should never reach the below without a step as `self.is_start_of_day` should
+            // always be true for daily resolution
+            let step = self.options.resolution.duration().unwrap();
+            // A session may not be a whole number of steps long, so go back to the last step
+            // before the current time within its session rather than subtracting a step.
+            let start = self
+                .boundaries()
+                .iter()
+                .copied()
+                .filter(|boundary| *boundary < self.datetime)
+                .max()
+                .unwrap();
+            let steps = div_ceil(self.datetime - start, step) - 1;
+            start + step * steps as i32
         }
     }
 
@@ -123,20 +206,12 @@ impl Clock {
     }
 
     pub fn next_datetime(&self) -> DateTime<Tz> {
-        if self.is_end_of_day() {
-            Eastern
-                .from_local_datetime(
-                    &self
-                        .calendar
-                        .advance_bdays(self.datetime.date().naive_local(), 1)
-                        .and_time(*OPENING_TIME),
-                )
-                .unwrap()
+        if self.is_warmup() && self.is_regular_close() {
+            self.warmup_next_day().1
+        } else if self.is_end_of_day() {
+            self.start_of_day(self.calendar.next_trading_day(self.date))
         } else {
-            match self.options.resolution {
-                Resolution::Minute => self.datetime + Duration::minutes(1),
-                Resolution::Day => unreachable!(),
-            }
+            self.step_forward()
         }
     }
 
@@ -147,8 +222,134 @@ impl Clock {
     pub fn is_open(&self) -> bool {
         match self.market_state {
             MarketState::Opening | MarketState::Open | MarketState::Closing => true,
-            MarketState::PreOpen | MarketState::Closed => false,
+            MarketState::Warmup
+            | MarketState::PreOpen
+            | MarketState::PreMarket
+            | MarketState::AfterHours
+            | MarketState::Closed => false,
+        }
+    }
+
+    pub fn is_warmup(&self) -> bool {
+        self.market_state == MarketState::Warmup
+    }
+
+    pub fn is_extended_hours(&self) -> bool {
+        matches!(
+            self.market_state,
+            MarketState::PreMarket | MarketState::AfterHours
+        )
+    }
+
+    fn opening(&self, date: NaiveDate) -> DateTime<Tz> {
+        self.calendar
+            .opening(date)
+            .expect("Clock should only be on trading days")
+    }
+
+    fn closing(&self, date: NaiveDate) -> DateTime<Tz> {
+        self.calendar
+            .closing(date)
+            .expect("Clock should only be on trading days")
+    }
+
+    /// The first tick of the day, which is the start of pre-market trading if extended hours are
+    /// simulated.
+    fn start_of_day(&self, date: NaiveDate) -> DateTime<Tz> {
+        if self.options.extended_hours {
+            self.calendar
+                .extended_opening(date)
+                .expect("Clock should only be on trading days")
+        } else {
+            self.opening(date)
+        }
+    }
+
+    /// The last tick of the day, which is the end of after-hours trading if extended hours are
+    /// simulated.
+    fn end_of_day(&self, date: NaiveDate) -> DateTime<Tz> {
+        if self.options.extended_hours {
+            self.calendar
+                .extended_closing(date)
+                .expect("Clock should only be on trading days")
+        } else {
+            self.closing(date)
+        }
+    }
+
+    /// The start of each session of the current day, followed by the end of the day.
+    fn boundaries(&self) -> [DateTime<Tz>; 4] {
+        [
+            self.start_of_day(self.date),
+            self.opening(self.date),
+            self.closing(self.date),
+            self.end_of_day(self.date),
+        ]
+    }
+
+    /// Whether the clock has reached the close of the regular session.
+    fn is_regular_close(&self) -> bool {
+        match self.options.resolution.duration() {
+            Some(_) => self.datetime == self.closing(self.date),
+            None => true,
+        }
+    }
+
+    /// The trading day and first tick after the current warmup day.
+    fn warmup_next_day(&self) -> (NaiveDate, DateTime<Tz>) {
+        let date = self.calendar.next_trading_day(self.date);
+        if date >= self.options.start {
+            (date, self.start_of_day(date))
+        } else {
+            (date, self.opening(date))
+        }
+    }
+
+    /// The number of ticks needed to step from `from` to `to`.
+    fn steps(&self, from: DateTime<Tz>, to: DateTime<Tz>, step: Duration) -> i64 {
+        if from >= to {
+            0
+        } else if self.options.sparse {
+            self.events.range((Excluded(from), Excluded(to))).count() as i64 + 1
+        } else {
+            div_ceil(to - from, step)
+        }
+    }
+
+    /// The next step within the current session, stopping at the end of the session and at any
+    /// events on the way.
+    fn step_forward(&self) -> DateTime<Tz> {
+        // We should never reach the below without a step as `self.is_end_of_day` should always be
+        // true for daily resolution
+        let step = self.options.resolution.duration().unwrap();
+        let mut next = self
+            .boundaries()
+            .iter()
+            .copied()
+            .filter(|boundary| *boundary > self.datetime)
+            .min()
+            .unwrap_or(self.datetime);
+        if !self.options.sparse {
+            // Steps are counted from the start of the session so that the clock returns to its
+            // grid after stopping at an event in between two steps
+            let start = self
+                .boundaries()
+                .iter()
+                .copied()
+                .filter(|boundary| *boundary <= self.datetime)
+                .max()
+                .unwrap_or(self.datetime);
+            let steps = (self.datetime - start).num_milliseconds() / step.num_milliseconds() + 1;
+            next = next.min(start + Duration::milliseconds(steps * step.num_milliseconds()))
+        }
+        if let Some(event) = self
+            .events
+            .range((Excluded(self.datetime), Unbounded))
+            .next()
+        {
+            next = next.min(*event)
         }
+        next
     }
 
     pub fn tick(&mut self) {
@@ -156,54 +357,91 @@ impl Clock {
             panic!("Market clock ticked after end of backtest");
         }
 
-        let state = &self.market_state;
-
-        if self.is_end_of_day() {
-            if let MarketState::Closed = state {
-                self.datetime = Eastern
-                    .from_local_datetime(
-                        &(self
-                            .calendar
-                            .advance_bdays(self.datetime.date().naive_local(), 1))
-                        .and_time(*OPENING_TIME),
-                    )
-                    .unwrap();
+        let extended_hours = self.options.extended_hours;
+        match self.market_state {
+            MarketState::Warmup => {
+                if self.is_regular_close() {
+                    let (date, datetime) = self.warmup_next_day();
+                    self.date = date;
+                    self.datetime = datetime;
+                    if date >= self.options.start {
+                        self.market_state = MarketState::PreOpen
+                    }
+                } else {
+                    self.datetime = self.step_forward()
+                }
             }
-            self.market_state = state.next();
-        } else {
-            match state {
-                MarketState::PreOpen | MarketState::Opening => self.market_state = state.next(),
-                _ => match self.options.resolution {
-                    Resolution::Minute => self.datetime = self.datetime + Duration::minutes(1),
-                    // We should never reach the below as `self.is_end_of_day` should always be
-                    // true for daily resolution
-                    Resolution::Day => unreachable!(),
-                },
+            MarketState::PreOpen if extended_hours => self.market_state = MarketState::PreMarket,
+            MarketState::PreOpen => self.market_state = MarketState::Opening,
+            MarketState::PreMarket => {
+                if self.datetime == self.opening(self.date) {
+                    self.market_state = MarketState::Opening
+                } else {
+                    self.datetime = self.step_forward()
+                }
+            }
+            MarketState::Opening => self.market_state = MarketState::Open,
+            MarketState::Open => {
+                if self.is_regular_close() {
+                    self.market_state = MarketState::Closing
+                } else {
+                    self.datetime = self.step_forward()
+                }
+            }
+            MarketState::Closing if extended_hours => self.market_state = MarketState::AfterHours,
+            MarketState::Closing => self.market_state = MarketState::Closed,
+            MarketState::AfterHours => {
+                if self.is_end_of_day() {
+                    self.market_state = MarketState::Closed
+                } else {
+                    self.datetime = self.step_forward()
+                }
+            }
+            MarketState::Closed => {
+                self.date = self.calendar.next_trading_day(self.date);
+                self.datetime = self.start_of_day(self.date);
+                self.market_state = MarketState::PreOpen
             }
         }
     }
 }
 
+fn div_ceil(duration: Duration, step: Duration) -> i64 {
+    let duration = duration.num_milliseconds();
+    let step = step.num_milliseconds();
+    (duration + step - 1) / step
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use chrono::Duration;
+    use crate::utils::exchange_calendar::Exchange;
+    use chrono::TimeZone;
+    use chrono_tz::US::Eastern;
 
     #[test]
     fn it_can_tell_and_update_time() {
         let mut clock = Clock::new(
-            NaiveDate::from_ymd(2021, 1, 1),
-            NaiveDate::from_ymd(2021, 12, 31),
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 12, 31).unwrap(),
             Duration::zero(),
             Resolution::Day,
+            Exchange::Nyse.calendar(),
+            false,
         );
         assert_eq!(
             clock.datetime().naive_local(),
-            NaiveDate::from_ymd(2021, 1, 5).and_hms(9, 30, 0)
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(9, 30, 0)
+                .unwrap()
         );
         assert_eq!(
             clock.next_datetime().naive_local(),
-            NaiveDate::from_ymd(2021, 1, 6).and_hms(9, 30, 0)
+            NaiveDate::from_ymd_opt(2021, 1, 6)
+                .unwrap()
+                .and_hms_opt(9, 30, 0)
+                .unwrap()
         );
 
         assert_eq!(clock.state(), MarketState::PreOpen);
@@ -232,18 +470,26 @@ mod test {
     #[test]
     fn it_works_for_intraday_data() {
         let mut clock = Clock::new(
-            NaiveDate::from_ymd(2021, 1, 1),
-            NaiveDate::from_ymd(2021, 12, 31),
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 12, 31).unwrap(),
             Duration::zero(),
             Resolution::Minute,
+            Exchange::Nyse.calendar(),
+            false,
         );
         assert_eq!(
             clock.datetime().naive_local(),
-            NaiveDate::from_ymd(2021, 1, 5).and_hms(9, 30, 0)
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(9, 30, 0)
+                .unwrap()
         );
         assert_eq!(
             clock.next_datetime().naive_local(),
-            NaiveDate::from_ymd(2021, 1, 5).and_hms(9, 31, 0)
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(9, 31, 0)
+                .unwrap()
         );
 
         assert_eq!(clock.state(), MarketState::PreOpen);
@@ -279,4 +525,359 @@ mod test {
         assert_eq!(clock.state(), MarketState::Open);
         assert!(clock.is_open());
     }
+
+    #[test]
+    fn it_works_for_hourly_data() {
+        let mut clock = Clock::new(
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+            Duration::zero(),
+            Resolution::Hour,
+            Exchange::Nyse.calendar(),
+            false,
+        );
+        assert_eq!(clock.simulation_periods(), 12);
+        clock.tick();
+        clock.tick();
+        for hour in 10..=15 {
+            clock.tick();
+            assert_eq!(
+                clock.datetime().naive_local(),
+                NaiveDate::from_ymd_opt(2021, 1, 5)
+                    .unwrap()
+                    .and_hms_opt(hour, 30, 0)
+                    .unwrap()
+            );
+        }
+        assert_eq!(
+            clock.next_datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(16, 0, 0)
+                .unwrap()
+        );
+        clock.tick();
+        assert!(clock.is_end_of_day());
+        assert_eq!(
+            clock.previous_datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(15, 30, 0)
+                .unwrap()
+        );
+        clock.tick();
+        assert_eq!(clock.state(), MarketState::Closing);
+    }
+
+    #[test]
+    fn it_works_for_custom_resolutions() {
+        let mut clock = Clock::new(
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+            Duration::zero(),
+            Resolution::Custom(Duration::seconds(30)),
+            Exchange::Nyse.calendar(),
+            false,
+        );
+        assert_eq!(clock.simulation_periods(), 785);
+        clock.tick();
+        clock.tick();
+        clock.tick();
+        assert_eq!(
+            clock.datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(9, 30, 30)
+                .unwrap()
+        );
+        assert_eq!(
+            clock.previous_datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(9, 30, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn it_closes_early_on_half_days() {
+        let mut clock = Clock::new(
+            NaiveDate::from_ymd_opt(2021, 11, 26).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 11, 29).unwrap(),
+            Duration::zero(),
+            Resolution::Minute,
+            Exchange::Nyse.calendar(),
+            false,
+        );
+        assert_eq!(clock.simulation_periods(), 215);
+        clock.tick();
+        for _ in 0..211 {
+            clock.tick();
+            assert_eq!(clock.state(), MarketState::Open);
+        }
+        assert!(clock.is_end_of_day());
+        assert_eq!(
+            clock.datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 11, 26)
+                .unwrap()
+                .and_hms_opt(13, 0, 0)
+                .unwrap()
+        );
+        clock.tick();
+        assert_eq!(clock.state(), MarketState::Closing);
+        clock.tick();
+        clock.tick();
+        assert_eq!(
+            clock.previous_datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 11, 26)
+                .unwrap()
+                .and_hms_opt(13, 0, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn it_works_for_markets_that_never_close() {
+        let mut clock = Clock::new(
+            NaiveDate::from_ymd_opt(2021, 1, 2).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 4).unwrap(),
+            Duration::zero(),
+            Resolution::Hour,
+            Exchange::AlwaysOpen.calendar(),
+            false,
+        );
+        assert_eq!(clock.simulation_periods(), 2 * 29);
+        assert_eq!(
+            clock.datetime().naive_utc(),
+            NaiveDate::from_ymd_opt(2021, 1, 2)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        );
+        for _ in 0..26 {
+            clock.tick();
+        }
+        assert_eq!(clock.state(), MarketState::Open);
+        assert!(clock.is_end_of_day());
+        assert_eq!(
+            clock.datetime().naive_utc(),
+            NaiveDate::from_ymd_opt(2021, 1, 3)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        );
+        clock.tick();
+        clock.tick();
+        clock.tick();
+        assert_eq!(clock.state(), MarketState::PreOpen);
+        assert_eq!(
+            clock.datetime().naive_utc(),
+            NaiveDate::from_ymd_opt(2021, 1, 3)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn it_steps_through_extended_hours() {
+        let mut clock = Clock::new(
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+            Duration::zero(),
+            Resolution::Minute,
+            Exchange::Nyse.calendar(),
+            true,
+        );
+        assert_eq!(clock.simulation_periods(), 960 + 7);
+        assert_eq!(
+            clock.datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(4, 0, 0)
+                .unwrap()
+        );
+        clock.tick();
+        for _ in 0..331 {
+            assert_eq!(clock.state(), MarketState::PreMarket);
+            assert!(clock.is_extended_hours());
+            assert!(!clock.is_open());
+            clock.tick();
+        }
+        assert_eq!(clock.state(), MarketState::Opening);
+        assert_eq!(
+            clock.datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(9, 30, 0)
+                .unwrap()
+        );
+        assert_eq!(
+            clock.previous_datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(9, 29, 0)
+                .unwrap()
+        );
+        for _ in 0..392 {
+            clock.tick();
+        }
+        assert_eq!(clock.state(), MarketState::Closing);
+        clock.tick();
+        assert_eq!(clock.state(), MarketState::AfterHours);
+        assert_eq!(
+            clock.datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(16, 0, 0)
+                .unwrap()
+        );
+        for _ in 0..240 {
+            clock.tick();
+            assert_eq!(clock.state(), MarketState::AfterHours);
+        }
+        assert!(clock.is_end_of_day());
+        assert_eq!(
+            clock.previous_datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(19, 59, 0)
+                .unwrap()
+        );
+        clock.tick();
+        assert_eq!(clock.state(), MarketState::Closed);
+        clock.tick();
+        assert_eq!(
+            clock.datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 6)
+                .unwrap()
+                .and_hms_opt(4, 0, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn it_skips_periods_without_events() {
+        let date = |hour, minute, second| {
+            Eastern
+                .with_ymd_and_hms(2021, 1, 5, hour, minute, second)
+                .unwrap()
+        };
+        let events = vec![
+            date(9, 45, 0),
+            date(11, 0, 0),
+            // Outside of the session
+            date(17, 0, 0),
+        ];
+        let mut clock = Clock::new(
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+            Duration::zero(),
+            Resolution::Minute,
+            Exchange::Nyse.calendar(),
+            false,
+        )
+        .sparse(events.into_iter().collect());
+        clock.schedule(date(14, 30, 0));
+        assert_eq!(clock.simulation_periods(), 4 + 5);
+        clock.tick();
+        clock.tick();
+        let mut times = Vec::new();
+        while clock.state() == MarketState::Open {
+            times.push(clock.datetime());
+            clock.tick();
+        }
+        assert_eq!(
+            times,
+            vec![
+                date(9, 30, 0),
+                date(9, 45, 0),
+                date(11, 0, 0),
+                date(14, 30, 0),
+                date(16, 0, 0),
+            ]
+        );
+        assert_eq!(clock.state(), MarketState::Closing);
+        assert_eq!(
+            clock.previous_datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(15, 59, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn it_returns_to_the_grid_after_events() {
+        let date = |hour, minute, second| {
+            Eastern
+                .with_ymd_and_hms(2021, 1, 5, hour, minute, second)
+                .unwrap()
+        };
+        let mut clock = Clock::new(
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+            Duration::zero(),
+            Resolution::Minute,
+            Exchange::Nyse.calendar(),
+            false,
+        );
+        clock.schedule(date(9, 31, 30));
+        // Outside of the session
+        clock.schedule(date(17, 0, 0));
+        assert_eq!(clock.events.len(), 1);
+        clock.tick();
+        clock.tick();
+        let mut times = Vec::new();
+        for _ in 0..5 {
+            times.push(clock.datetime());
+            clock.tick();
+        }
+        assert_eq!(
+            times,
+            vec![
+                date(9, 30, 0),
+                date(9, 31, 0),
+                date(9, 31, 30),
+                date(9, 32, 0),
+                date(9, 33, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_warms_up_before_the_start() {
+        let mut clock = Clock::new(
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+            Duration::days(7),
+            Resolution::Day,
+            Exchange::Nyse.calendar(),
+            false,
+        );
+        assert_eq!(clock.simulation_periods(), 4 + 5);
+        let mut dates = Vec::new();
+        while clock.is_warmup() {
+            assert!(!clock.is_open());
+            dates.push(clock.datetime().date_naive());
+            clock.tick();
+        }
+        assert_eq!(
+            dates,
+            vec![
+                NaiveDate::from_ymd_opt(2020, 12, 29).unwrap(),
+                NaiveDate::from_ymd_opt(2020, 12, 30).unwrap(),
+                NaiveDate::from_ymd_opt(2020, 12, 31).unwrap(),
+                NaiveDate::from_ymd_opt(2021, 1, 4).unwrap(),
+            ]
+        );
+        assert_eq!(clock.state(), MarketState::PreOpen);
+        assert_eq!(
+            clock.datetime().naive_local(),
+            NaiveDate::from_ymd_opt(2021, 1, 5)
+                .unwrap()
+                .and_hms_opt(9, 30, 0)
+                .unwrap()
+        );
+    }
 }
@@ -1,84 +1,147 @@
-use crate::data::Aggregate;
-use crate::{Options, Resolution};
+use crate::data::{
+    corporate_actions::{back_adjust, CorporateAction, CorporateActions},
+    error::Error,
+    history::last_bars,
+    provider::DataProvider,
+    report::{DownloadError, DownloadErrorKind, DownloadFailure, DownloadReport},
+    resample::{resample, resample_days, resample_sessions, Timeframe},
+    validation::{validate, DataIssue, DataIssueKind, ValidationReport},
+    Aggregate, MarketData,
+};
+use crate::{DownloadPolicy, MissingBarPolicy, Options};
 use chrono::prelude::*;
-use chrono_tz::{Tz, US::Eastern};
-use futures::{stream, StreamExt, TryStreamExt};
-use polygon::rest::{client, GetAggregate, Timespan};
-use std::collections::{BTreeMap, HashMap};
-use stream_flatten_iters::TryStreamExt as _;
-
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
-pub struct DownloadJob {
-    ticker: String,
-    start: NaiveDate,
-    end: NaiveDate,
-    resolution: Resolution,
-}
+use chrono::Duration;
+use chrono_tz::Tz;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tracing::warn;
 
 pub struct DataManager {
-    download_jobs: Vec<GetAggregate>,
+    data_options: Options,
+    data_provider: Box<dyn DataProvider>,
     data: HashMap<String, BTreeMap<DateTime<Tz>, Aggregate>>,
+    corporate_actions: CorporateActions,
+    /// The number of corporate actions, in order of ex-date, handed out to be applied
+    applied_actions: usize,
+    duplicates: Vec<DataIssue>,
 }
 
 impl DataManager {
-    pub fn new(data_options: Options) -> Self {
-        let download_jobs = data_options
-            .tickers
-            .iter()
-            .map(|ticker| {
-                let timespan = match data_options.resolution {
-                    Resolution::Day => Timespan::Day,
-                    Resolution::Minute => Timespan::Minute,
-                };
-                let start = data_options.start.and_hms(0, 0, 0);
-                let end = data_options.end.and_hms(0, 0, 0);
-                GetAggregate::new(ticker, start, end)
-                    .timespan(timespan)
-                    .limit(50000)
-            })
-            .collect();
-
+    pub fn new(data_options: Options, data_provider: Box<dyn DataProvider>) -> Self {
         Self {
-            download_jobs,
+            data_options,
+            data_provider,
             data: HashMap::new(),
+            corporate_actions: CorporateActions::default(),
+            applied_actions: 0,
+            duplicates: Vec::new(),
         }
     }
 
-    pub async fn download_data(&mut self) {
-        let jobs = self.download_jobs.clone();
-        let client = client(&std::env::var("POLYGON_TOKEN").expect(
-            "The Polygon data provider requires the POLYGON_TOKEN environment variable to be set",
-        ))
-        .show_progress();
-        let data = stream::select_all(client.send_all_paginated(jobs.iter()).map(|stream| {
-            stream
-                .map_ok(|wrapper| {
-                    let ticker = wrapper.ticker.clone();
-                    wrapper
-                        .results
-                        .into_iter()
-                        .map(move |r| (ticker.clone(), r))
-                })
-                .try_flatten_iters()
-        }))
-        .filter_map(|x| async move { x.ok() })
-        .map(|x| async { x })
-        .buffer_unordered(500);
-
-        let mut data = Box::pin(data);
-        while let Some((ticker, agg)) = data.next().await {
-            self.data
-                .entry(ticker.to_string())
-                .and_modify(|map| {
-                    let agg = agg.clone();
-                    map.insert(agg.t.with_timezone(&Eastern), From::from(agg));
-                })
-                .or_insert_with(|| {
-                    let mut map = BTreeMap::new();
-                    map.insert(agg.t.with_timezone(&Eastern), From::from(agg));
-                    map
-                });
+    /// Downloads the data for all tickers, applying the `download_policy` of the options to any
+    /// failures. Returns a report of the failures that were tolerated.
+    pub async fn download_data(&mut self) -> Result<DownloadReport, Error> {
+        // Strategies need history from before the start of the backtest to warm up
+        let mut options = self.data_options.clone();
+        options.start = self.data_options.data_start();
+        let mut request = options.clone();
+        let mut data = MarketData::new();
+        let mut attempt = 0;
+        let report = loop {
+            let (downloaded, mut report) = match self.data_provider.download_data(&request).await {
+                Ok(downloaded) => (downloaded, DownloadReport::default()),
+                Err(Error::Download(e)) => (e.data, e.report),
+                Err(e) => return Err(e),
+            };
+            for ticker in request.tickers.iter() {
+                let failed = report.failures.iter().any(|f| &f.ticker == ticker);
+                let missing = downloaded.get(ticker).filter(|x| !x.is_empty()).is_none();
+                if !failed && missing {
+                    report.failures.push(DownloadFailure {
+                        ticker: ticker.clone(),
+                        page: None,
+                        kind: DownloadErrorKind::NoData,
+                        message: "No data returned".to_string(),
+                    })
+                }
+            }
+            data.extend(downloaded);
+            if report.is_empty() {
+                break report;
+            }
+            match self.data_options.download_policy {
+                DownloadPolicy::Retry { retries, backoff } if attempt < retries => {
+                    let factor = 2i64.saturating_pow(attempt);
+                    let wait =
+                        Duration::milliseconds(backoff.num_milliseconds().saturating_mul(factor));
+                    attempt += 1;
+                    warn!(%report, attempt, "Retrying failed downloads in {}s", wait.num_seconds());
+                    tokio::time::sleep(wait.to_std().unwrap_or_default()).await;
+                    request.tickers = report.tickers();
+                }
+                DownloadPolicy::Warn => {
+                    warn!(%report, "Continuing with incomplete data");
+                    break report;
+                }
+                DownloadPolicy::Fail | DownloadPolicy::Retry { .. } => {
+                    return Err(Error::Download(Box::new(DownloadError { report, data })))
+                }
+            }
+        };
+        for (ticker, aggregates) in data {
+            let map = self.data.entry(ticker.clone()).or_default();
+            for agg in aggregates {
+                let datetime = agg.datetime;
+                if map.insert(datetime, agg).is_some() {
+                    self.duplicates.push(DataIssue::new(
+                        &ticker,
+                        datetime,
+                        DataIssueKind::DuplicateTimestamp,
+                        "Multiple bars for the same timestamp",
+                    ))
+                }
+            }
         }
+        // Providers may return finer bars than the resolution, e.g. minute bars for hourly ticks,
+        // and stamp daily bars at midnight rather than at the opening the clock ticks at
+        let calendar = self.data_options.exchange.calendar();
+        for series in self.data.values_mut() {
+            let resampled = match self.data_options.resolution.duration() {
+                Some(step) => resample_sessions(
+                    series.values(),
+                    step,
+                    calendar.as_ref(),
+                    self.data_options.extended_hours,
+                ),
+                None => resample_days(series.values(), calendar.as_ref()),
+            };
+            *series = resampled
+                .into_iter()
+                .map(|agg| (agg.datetime, agg))
+                .collect();
+        }
+        self.corporate_actions = self
+            .data_provider
+            .download_corporate_actions(&options)
+            .await?;
+        self.corporate_actions
+            .actions
+            .sort_by_key(|action| action.ex_date());
+        self.applied_actions = 0;
+        if self.data_options.normalize {
+            for (ticker, series) in self.data.iter_mut() {
+                back_adjust(series, self.corporate_actions.for_ticker(ticker));
+            }
+        }
+        Ok(report)
+    }
+
+    /// Returns every time at which any ticker has a bar.
+    pub fn timestamps(&self) -> BTreeSet<DateTime<Tz>> {
+        self.data
+            .values()
+            .flat_map(|series| series.keys().copied())
+            .collect()
     }
 
     pub fn get_data(
@@ -101,8 +164,102 @@ impl DataManager {
         }
     }
 
+    /// Whether `ticker` has any bars between `start` and `end`.
+    pub fn has_data(&self, ticker: &str, start: DateTime<Tz>, end: DateTime<Tz>) -> bool {
+        self.data
+            .get(ticker)
+            .is_some_and(|series| series.range(start..=end).next().is_some())
+    }
+
+    /// Runs the checks configured in the `validation` options over the downloaded data. Returns
+    /// `Error::Validation` if `abort_on_error` is set and any errors were found.
+    pub fn validate(&self) -> Result<ValidationReport, Error> {
+        let validation = &self.data_options.validation;
+        let calendar = self.data_options.exchange.calendar();
+        let mut report = ValidationReport::default();
+        if validation.duplicates {
+            report.issues.extend(self.duplicates.iter().cloned());
+        }
+        let mut tickers: Vec<&String> = self.data.keys().collect();
+        tickers.sort();
+        for ticker in tickers {
+            report.issues.extend(validate(
+                ticker,
+                &self.data[ticker],
+                self.data_options.resolution,
+                calendar.as_ref(),
+                validation,
+                self.data_options.extended_hours,
+            ));
+        }
+        for (kind, count) in report.counts() {
+            warn!(?kind, count, "Data validation found issues");
+        }
+        if validation.abort_on_error && report.has_errors() {
+            return Err(Error::Validation(Box::new(report)));
+        }
+        Ok(report)
+    }
+
+    /// Returns the corporate actions that went ex on or before `date` and weren't returned before,
+    /// so that actions going ex on a non-trading day or during the warmup are applied on the next
+    /// call. These only need to be applied to the account when the prices have not already been
+    /// normalized for them.
+    pub fn get_corporate_actions(&mut self, date: NaiveDate) -> Vec<CorporateAction> {
+        if self.data_options.normalize {
+            return Vec::new();
+        }
+        let pending = &self.corporate_actions.actions[self.applied_actions..];
+        let count = pending
+            .iter()
+            .take_while(|action| action.ex_date() <= date)
+            .count();
+        self.applied_actions += count;
+        pending[..count].to_vec()
+    }
+
+    /// Returns the last `n_bars` bars of the given timeframe up to and including `end`.
+    pub fn history(
+        &self,
+        ticker: &str,
+        end: DateTime<Tz>,
+        n_bars: usize,
+        timeframe: Timeframe,
+    ) -> Option<Vec<Aggregate>> {
+        let series = self.data.get(ticker)?;
+        let calendar = self.data_options.exchange.calendar();
+        let data = last_bars(series, end, n_bars, timeframe, calendar.as_ref());
+        if data.is_empty() {
+            None
+        } else {
+            Some(data)
+        }
+    }
+
+    /// Returns the bars between `start` and `end` consolidated into the given timeframe.
+    pub fn get_resampled_data(
+        &self,
+        ticker: &str,
+        start: DateTime<Tz>,
+        end: DateTime<Tz>,
+        timeframe: Timeframe,
+    ) -> Option<Vec<Aggregate>> {
+        let bars = self
+            .data
+            .get(ticker)?
+            .range(start..=end)
+            .map(|(_, agg)| agg);
+        let calendar = self.data_options.exchange.calendar();
+        let data = resample(bars, timeframe, calendar.as_ref());
+        if data.is_empty() {
+            None
+        } else {
+            Some(data)
+        }
+    }
+
     pub fn get_last_before(&self, ticker: &str, datetime: DateTime<Tz>) -> Option<Aggregate> {
-        let start = chrono::MIN_DATETIME.with_timezone(&datetime.timezone());
+        let start = DateTime::<Utc>::MIN_UTC.with_timezone(&datetime.timezone());
         self.data
             .get(ticker)?
             .range(start..=datetime)
@@ -110,4 +267,244 @@ impl DataManager {
             .map(|(_, agg)| agg)
             .cloned()
     }
+
+    /// Returns the bar for `ticker` at `datetime`, filling in missing bars according to the
+    /// `missing_bar_policy` of the options.
+    pub fn get_bar(&self, ticker: &str, datetime: DateTime<Tz>) -> Option<Aggregate> {
+        let last = self.get_last_before(ticker, datetime)?;
+        if last.datetime == datetime {
+            return Some(last);
+        }
+        match self.data_options.missing_bar_policy {
+            MissingBarPolicy::Strict => return None,
+            MissingBarPolicy::Stale { max_age } if datetime - last.datetime > max_age => {
+                return None
+            }
+            MissingBarPolicy::ForwardFill | MissingBarPolicy::Stale { .. } => (),
+        }
+        Some(Aggregate {
+            datetime,
+            open: last.close,
+            high: last.close,
+            low: last.close,
+            close: last.close,
+            volume: Decimal::ZERO,
+        })
+    }
+
+    /// Returns how long before `datetime` the last bar for `ticker` was.
+    pub fn get_price_age(&self, ticker: &str, datetime: DateTime<Tz>) -> Option<Duration> {
+        self.get_last_before(ticker, datetime)
+            .map(|last| datetime - last.datetime)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::corporate_actions::CorporateActionsExt;
+    use crate::data::testing::{bar, flat_bar, opening};
+    use async_trait::async_trait;
+    use chrono_tz::US::Eastern;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Fails "FLAKY" on the first attempt and always returns nothing for "BAD".
+    struct FlakyProvider {
+        attempts: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl DataProvider for FlakyProvider {
+        async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst);
+            let mut data = MarketData::new();
+            let mut report = DownloadReport::default();
+            for ticker in meta.tickers.iter() {
+                match ticker.as_str() {
+                    "BAD" => (),
+                    "FLAKY" if attempt == 0 => report.failures.push(DownloadFailure {
+                        ticker: ticker.clone(),
+                        page: Some(1),
+                        kind: DownloadErrorKind::Request,
+                        message: "Timed out".into(),
+                    }),
+                    _ => {
+                        data.insert(ticker.clone(), vec![flat_bar(opening(), 1)]);
+                    }
+                }
+            }
+            if report.is_empty() {
+                Ok(data)
+            } else {
+                Err(Error::Download(Box::new(DownloadError { report, data })))
+            }
+        }
+    }
+
+    fn data_manager(tickers: &[&str], policy: DownloadPolicy) -> (DataManager, Arc<AtomicUsize>) {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let options = Options::new(
+            tickers.iter().map(|t| t.to_string()).collect(),
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 5).unwrap(),
+        )
+        .set_download_policy(policy);
+        let provider = FlakyProvider {
+            attempts: attempts.clone(),
+        };
+        (DataManager::new(options, Box::new(provider)), attempts)
+    }
+
+    #[tokio::test]
+    async fn it_applies_the_download_policy() {
+        let (mut dm, _) = data_manager(&["AAPL", "FLAKY"], DownloadPolicy::Fail);
+        match dm.download_data().await {
+            Err(Error::Download(e)) => {
+                assert_eq!(e.report.tickers(), vec!["FLAKY".to_string()]);
+                assert!(e.data.contains_key("AAPL"));
+            }
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let (mut dm, attempts) = data_manager(
+            &["AAPL", "FLAKY"],
+            DownloadPolicy::Retry {
+                retries: 2,
+                backoff: Duration::zero(),
+            },
+        );
+        let report = dm.download_data().await.unwrap();
+        assert!(report.is_empty());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(dm
+            .get_last_before(
+                "FLAKY",
+                Eastern.with_ymd_and_hms(2021, 1, 6, 0, 0, 0).unwrap()
+            )
+            .is_some());
+        assert!(dm
+            .get_last_before(
+                "AAPL",
+                Eastern.with_ymd_and_hms(2021, 1, 6, 0, 0, 0).unwrap()
+            )
+            .is_some());
+
+        let (mut dm, _) = data_manager(&["AAPL", "BAD"], DownloadPolicy::Warn);
+        let report = dm.download_data().await.unwrap();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].kind, DownloadErrorKind::NoData);
+        assert!(dm
+            .get_last_before(
+                "AAPL",
+                Eastern.with_ymd_and_hms(2021, 1, 6, 0, 0, 0).unwrap()
+            )
+            .is_some());
+    }
+
+    #[test]
+    fn it_applies_the_missing_bar_policy() {
+        let (mut dm, _) = data_manager(&["AAPL"], DownloadPolicy::Fail);
+        let opening = opening();
+        let agg = bar(opening, 1, 2, 1, 2);
+        dm.data
+            .entry("AAPL".to_string())
+            .or_default()
+            .insert(opening, agg);
+        let later = opening + Duration::minutes(5);
+
+        assert_eq!(dm.get_bar("AAPL", opening).unwrap().open, Decimal::ONE);
+        let filled = dm.get_bar("AAPL", later).unwrap();
+        assert_eq!(filled.open, Decimal::TWO);
+        assert!(filled.volume.is_zero());
+        assert_eq!(dm.get_price_age("AAPL", later), Some(Duration::minutes(5)));
+        assert_eq!(
+            dm.get_price_age("AAPL", opening - Duration::minutes(1)),
+            None
+        );
+
+        dm.data_options.missing_bar_policy = MissingBarPolicy::Strict;
+        assert!(dm.get_bar("AAPL", opening).is_some());
+        assert!(dm.get_bar("AAPL", later).is_none());
+
+        dm.data_options.missing_bar_policy = MissingBarPolicy::Stale {
+            max_age: Duration::minutes(5),
+        };
+        assert!(dm.get_bar("AAPL", later).is_some());
+        assert!(dm.get_bar("AAPL", later + Duration::minutes(1)).is_none());
+    }
+
+    /// Returns daily bars for 2021-01-04 and 2021-01-05 stamped at midnight, as Polygon does.
+    struct MidnightProvider;
+
+    #[async_trait]
+    impl DataProvider for MidnightProvider {
+        async fn download_data(&self, meta: &Options) -> Result<MarketData, Error> {
+            let bars = (4..6)
+                .map(|day| {
+                    let midnight = Eastern.with_ymd_and_hms(2021, 1, day, 0, 0, 0).unwrap();
+                    bar(
+                        midnight,
+                        day as i64,
+                        day as i64 + 1,
+                        day as i64,
+                        day as i64 + 1,
+                    )
+                })
+                .collect();
+            let mut data = MarketData::new();
+            data.insert(meta.tickers[0].clone(), bars);
+            Ok(data)
+        }
+    }
+
+    #[tokio::test]
+    async fn it_stamps_daily_bars_at_the_opening() {
+        let options = Options::new(
+            vec!["AAPL".to_string()],
+            NaiveDate::from_ymd_opt(2021, 1, 4).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 6).unwrap(),
+        )
+        .set_missing_bar_policy(MissingBarPolicy::Strict);
+        let mut dm = DataManager::new(options, Box::new(MidnightProvider));
+        dm.download_data().await.unwrap();
+
+        let opening = Eastern.with_ymd_and_hms(2021, 1, 5, 9, 30, 0).unwrap();
+        let bar = dm.get_bar("AAPL", opening).unwrap();
+        assert_eq!(bar.datetime, opening);
+        assert_eq!(bar.open, Decimal::new(5, 0));
+        assert_eq!(bar.volume, Decimal::ONE);
+        assert!(dm
+            .get_bar(
+                "AAPL",
+                Eastern.with_ymd_and_hms(2021, 1, 6, 9, 30, 0).unwrap()
+            )
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn it_applies_corporate_actions_going_ex_on_a_weekend() {
+        let split = CorporateAction::Split {
+            ticker: "AAPL".into(),
+            ex_date: NaiveDate::from_ymd_opt(2021, 1, 9).unwrap(),
+            ratio: Decimal::new(2, 0),
+        };
+        let options = Options::new(
+            vec!["AAPL".to_string()],
+            NaiveDate::from_ymd_opt(2021, 1, 4).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 1, 12).unwrap(),
+        );
+        let provider =
+            MidnightProvider.with_corporate_actions(CorporateActions::new(vec![split.clone()]));
+        let mut dm = DataManager::new(options, Box::new(provider));
+        dm.download_data().await.unwrap();
+
+        let friday = NaiveDate::from_ymd_opt(2021, 1, 8).unwrap();
+        assert!(dm.get_corporate_actions(friday).is_empty());
+        let monday = NaiveDate::from_ymd_opt(2021, 1, 11).unwrap();
+        assert_eq!(dm.get_corporate_actions(monday), vec![split]);
+        assert!(dm
+            .get_corporate_actions(monday.succ_opt().unwrap())
+            .is_empty());
+    }
 }
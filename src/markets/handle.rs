@@ -1,42 +1,99 @@
-use crate::data::Aggregate;
+use crate::data::{
+    corporate_actions::CorporateAction, history::History, resample::Timeframe, Aggregate,
+};
 use crate::markets::clock::MarketState;
-use chrono::DateTime;
+use chrono::{DateTime, Duration};
 use chrono_tz::Tz;
 use rust_decimal::Decimal;
+use std::collections::HashMap;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot::{self, Sender as OneshotSender};
 
+/// The state of a ticker at the current time.
+///
+/// `price` and `age` describe the bar stamped at the current time, which orders fill against.
+/// With `prevent_look_ahead` that bar may still be forming, so `bar` is the last one that has
+/// closed instead.
+#[derive(Clone, Debug)]
+pub struct TickerSnapshot {
+    /// The bar stamped at the current time or, with `prevent_look_ahead`, the last bar that has
+    /// closed by the current time, if any
+    pub bar: Option<Aggregate>,
+    /// The price orders currently fill at: the open of the bar stamped at the current time while
+    /// it is still forming, and its close otherwise
+    pub price: Decimal,
+    /// How long before the current time the bar behind `price` started, which is more than zero
+    /// when that bar has been filled in from an earlier one
+    pub age: Duration,
+}
+
 #[derive(Clone, Debug)]
 pub(crate) enum MarketRequest {
+    Bars {
+        tickers: Vec<String>,
+    },
+    CorporateActions,
     Datetime,
     IsDone,
+    IsExtendedHours,
     IsOpen,
     Data {
         ticker: String,
         start: DateTime<Tz>,
         end: DateTime<Tz>,
     },
-    GetOpen {
+    ResampledData {
         ticker: String,
+        start: DateTime<Tz>,
+        end: DateTime<Tz>,
+        timeframe: Timeframe,
     },
-    GetCurrent {
+    GetOpen {
         ticker: String,
     },
     GetLast {
         ticker: String,
     },
+    History {
+        ticker: String,
+        n_bars: usize,
+        timeframe: Timeframe,
+    },
+    Histories {
+        tickers: Vec<String>,
+        n_bars: usize,
+        timeframe: Timeframe,
+    },
+    MarkPrices {
+        tickers: Vec<String>,
+    },
     NextDatetime,
+    Snapshot {
+        tickers: Vec<String>,
+    },
+    PriceAge {
+        ticker: String,
+    },
     State,
     PreviousDatetime,
+    Schedule {
+        datetime: DateTime<Tz>,
+    },
     Tick,
 }
 
 #[derive(Clone, Debug)]
 pub(crate) enum MarketResponse {
+    Bars(HashMap<String, Aggregate>),
     Bool(bool),
+    CorporateActions(Vec<CorporateAction>),
     Data(Option<Vec<Aggregate>>),
     Datetime(DateTime<Tz>),
+    History(History),
+    MaybeDuration(Option<Duration>),
     MaybePrice(Option<Decimal>),
+    Prices(HashMap<String, Decimal>),
+    Snapshot(HashMap<String, TickerSnapshot>),
     State(MarketState),
     // Generic reply for when no reply is needed
     Success,
@@ -105,6 +162,16 @@ impl Market {
         }
     }
 
+    /// Whether the market is in its pre-market or after-hours session.
+    pub async fn is_extended_hours(&self) -> bool {
+        let response = self.send_request(MarketRequest::IsExtendedHours).await;
+        if let MarketResponse::Bool(b) = response {
+            b
+        } else {
+            unreachable!()
+        }
+    }
+
     pub async fn get_data<T: ToString>(
         &self,
         ticker: T,
@@ -125,14 +192,101 @@ impl Market {
         }
     }
 
-    pub(crate) async fn get_current_price(&self, ticker: &str) -> Option<Decimal> {
+    /// Returns the data between `start` and `end` consolidated into bars of the given timeframe,
+    /// which should be coarser than the resolution of the simulation.
+    pub async fn get_resampled_data<T: ToString>(
+        &self,
+        ticker: T,
+        start: DateTime<Tz>,
+        end: DateTime<Tz>,
+        timeframe: Timeframe,
+    ) -> Option<Vec<Aggregate>> {
+        let response = self
+            .send_request(MarketRequest::ResampledData {
+                ticker: ticker.to_string(),
+                start,
+                end,
+                timeframe,
+            })
+            .await;
+        if let MarketResponse::Data(data) = response {
+            data
+        } else {
+            unreachable!()
+        }
+    }
+
+    /// Returns the last `n_bars` bars of the given timeframe up to the current time, skipping over
+    /// weekends, holidays and time outside of the sessions. The last bar may still be forming.
+    pub async fn history<T: ToString>(
+        &self,
+        ticker: T,
+        n_bars: usize,
+        timeframe: Timeframe,
+    ) -> Option<Vec<Aggregate>> {
         let response = self
-            .send_request(MarketRequest::GetCurrent {
+            .send_request(MarketRequest::History {
                 ticker: ticker.to_string(),
+                n_bars,
+                timeframe,
             })
             .await;
-        if let MarketResponse::MaybePrice(p) = response {
-            p
+        if let MarketResponse::Data(data) = response {
+            data
+        } else {
+            unreachable!()
+        }
+    }
+
+    /// Like `history`, but for several tickers with their bars aligned on the same datetimes.
+    pub async fn histories<T: ToString>(
+        &self,
+        tickers: &[T],
+        n_bars: usize,
+        timeframe: Timeframe,
+    ) -> History {
+        let response = self
+            .send_request(MarketRequest::Histories {
+                tickers: tickers.iter().map(|t| t.to_string()).collect(),
+                n_bars,
+                timeframe,
+            })
+            .await;
+        if let MarketResponse::History(history) = response {
+            history
+        } else {
+            unreachable!()
+        }
+    }
+
+    /// Returns the current state of each of the tickers in a single request. Tickers without any
+    /// price are left out.
+    pub async fn snapshot<T: ToString>(&self, tickers: &[T]) -> HashMap<String, TickerSnapshot> {
+        let response = self
+            .send_request(MarketRequest::Snapshot {
+                tickers: tickers.iter().map(|t| t.to_string()).collect(),
+            })
+            .await;
+        if let MarketResponse::Snapshot(snapshot) = response {
+            snapshot
+        } else {
+            unreachable!()
+        }
+    }
+
+    /// Returns the bars at the current time, which may still be forming. Used to fill orders
+    /// within a bar, so it ignores look-ahead protection.
+    pub(crate) async fn get_current_bars<T: ToString>(
+        &self,
+        tickers: &[T],
+    ) -> HashMap<String, Aggregate> {
+        let response = self
+            .send_request(MarketRequest::Bars {
+                tickers: tickers.iter().map(|t| t.to_string()).collect(),
+            })
+            .await;
+        if let MarketResponse::Bars(bars) = response {
+            bars
         } else {
             unreachable!()
         }
@@ -151,6 +305,22 @@ impl Market {
         }
     }
 
+    /// Returns the price to value a position in each of the tickers at: the current price, or the
+    /// last known close where the `missing_bar_policy` leaves the ticker without a current bar.
+    /// Tickers without any bars yet are left out.
+    pub async fn mark_prices<T: ToString>(&self, tickers: &[T]) -> HashMap<String, Decimal> {
+        let response = self
+            .send_request(MarketRequest::MarkPrices {
+                tickers: tickers.iter().map(|t| t.to_string()).collect(),
+            })
+            .await;
+        if let MarketResponse::Prices(prices) = response {
+            prices
+        } else {
+            unreachable!()
+        }
+    }
+
     pub async fn get_open(&self, ticker: &str) -> Option<Decimal> {
         let response = self
             .send_request(MarketRequest::GetOpen {
@@ -164,6 +334,31 @@ impl Market {
         }
     }
 
+    /// Returns how old the last bar for `ticker` is, or `None` if there is no data for it yet.
+    pub async fn get_price_age(&self, ticker: &str) -> Option<Duration> {
+        let response = self
+            .send_request(MarketRequest::PriceAge {
+                ticker: ticker.to_string(),
+            })
+            .await;
+        if let MarketResponse::MaybeDuration(age) = response {
+            age
+        } else {
+            unreachable!()
+        }
+    }
+
+    /// Returns the corporate actions going ex on the current date that are not already reflected
+    /// in the prices.
+    pub async fn get_corporate_actions(&self) -> Vec<CorporateAction> {
+        let response = self.send_request(MarketRequest::CorporateActions).await;
+        if let MarketResponse::CorporateActions(actions) = response {
+            actions
+        } else {
+            unreachable!()
+        }
+    }
+
     pub(crate) async fn is_done(&self) -> bool {
         let response = self.send_request(MarketRequest::IsDone).await;
         if let MarketResponse::Bool(b) = response {
@@ -173,6 +368,13 @@ impl Market {
         }
     }
 
+    /// Makes sure the simulation has a tick at `datetime`, even when the clock would otherwise
+    /// skip over it. Has no effect if `datetime` is outside of a session or has already passed.
+    pub async fn schedule(&self, datetime: DateTime<Tz>) {
+        self.send_request(MarketRequest::Schedule { datetime })
+            .await;
+    }
+
     pub(crate) async fn tick(&self) {
         self.send_request(MarketRequest::Tick).await;
     }
@@ -1,11 +1,120 @@
+use crate::utils::exchange_calendar::Exchange;
 use chrono::{Duration, NaiveDate};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use serde_with::{serde_as, DurationSeconds};
+use serde_with::{serde_as, DurationMilliSeconds, DurationSeconds};
 
+#[serde_as]
 #[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, Hash, PartialEq)]
 pub enum Resolution {
+    Second,
     Minute,
+    Hour,
     Day,
+    /// Steps of a fixed duration through each session.
+    Custom(#[serde_as(as = "DurationMilliSeconds<i64>")] Duration),
+}
+
+impl Resolution {
+    /// The time between ticks during a session, or `None` if there is one tick per day.
+    pub fn duration(&self) -> Option<Duration> {
+        match self {
+            Self::Second => Some(Duration::seconds(1)),
+            Self::Minute => Some(Duration::minutes(1)),
+            Self::Hour => Some(Duration::hours(1)),
+            Self::Day => None,
+            Self::Custom(duration) => Some(*duration),
+        }
+    }
+}
+
+/// How the clock steps through each session.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum ClockMode {
+    /// Tick at every step of the resolution.
+    Dense,
+    /// Only tick when any ticker has a bar or a callback is scheduled, as well as at the session
+    /// boundaries of each day.
+    Sparse,
+}
+
+/// What to do when some of the data for a backtest could not be downloaded.
+#[serde_as]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum DownloadPolicy {
+    /// Return an error from `Simulator::new`.
+    Fail,
+    /// Log the failures and run the backtest on the data that was downloaded.
+    Warn,
+    /// Retry the failed tickers up to `retries` times, doubling the wait after each attempt,
+    /// before failing.
+    Retry {
+        retries: u32,
+        #[serde_as(as = "DurationMilliSeconds<i64>")]
+        backoff: Duration,
+    },
+}
+
+/// How to price a ticker that has no bar at the requested time.
+#[serde_as]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum MissingBarPolicy {
+    /// Only use the bar at exactly the requested time.
+    Strict,
+    /// Use the last close before the requested time as a bar without volume.
+    ForwardFill,
+    /// Forward-fill, but only from bars that are at most `max_age` old.
+    Stale {
+        #[serde_as(as = "DurationSeconds<i64>")]
+        max_age: Duration,
+    },
+}
+
+/// Which end of its interval a bar's timestamp marks.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum BarTimestamp {
+    /// Bars are stamped with the start of their interval, as Polygon does, so the close of a bar
+    /// is only known one step after its timestamp.
+    Open,
+    /// Bars are stamped with the end of their interval, so the close is known at the timestamp.
+    Close,
+}
+
+/// Checks run on the data before a backtest starts.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct Validation {
+    /// Flag bars whose low or high does not contain the open and close.
+    pub ohlc: bool,
+    /// Flag bars with a price of zero or less.
+    pub non_positive_prices: bool,
+    /// Flag bars without any volume. Off by default, as thinly traded minute bars often have none.
+    pub zero_volume: bool,
+    /// Flag timestamps with more than one bar.
+    pub duplicates: bool,
+    /// Flag bars outside of the exchange's trading sessions, including extended hours if they are
+    /// simulated.
+    pub sessions: bool,
+    /// Flag bars preceded by more than this many missing bars.
+    pub max_missing_bars: Option<u32>,
+    /// Flag bars whose close moved more than this fraction from the previous close.
+    pub max_jump: Option<Decimal>,
+    /// Return an error from `Simulator::new` if any issue with error severity is found.
+    pub abort_on_error: bool,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Self {
+            ohlc: true,
+            non_positive_prices: true,
+            zero_volume: false,
+            duplicates: true,
+            sessions: true,
+            max_missing_bars: None,
+            max_jump: Some(Decimal::new(5, 1)),
+            abort_on_error: false,
+        }
+    }
 }
 
 #[serde_as]
@@ -14,11 +123,25 @@ pub struct Options {
     pub tickers: Vec<String>,
     pub start: NaiveDate,
     pub end: NaiveDate,
+    /// How much history before `start` to download and tick strategies through without trading.
     #[serde_as(as = "DurationSeconds<i64>")]
     pub warmup: Duration,
     pub resolution: Resolution,
+    pub clock_mode: ClockMode,
+    /// The exchange whose trading days and session times the simulation follows.
+    pub exchange: Exchange,
+    /// Also simulate the pre-market and after-hours sessions for intraday resolutions.
+    pub extended_hours: bool,
+    /// Back-adjust prices and volumes for splits and dividends.
     pub normalize: bool,
     pub outdir: Option<String>,
+    pub download_policy: DownloadPolicy,
+    pub validation: Validation,
+    pub missing_bar_policy: MissingBarPolicy,
+    /// Clamp market queries to the data that would have been known at the current time. On by
+    /// default.
+    pub prevent_look_ahead: bool,
+    pub bar_timestamp: BarTimestamp,
 }
 
 impl Options {
@@ -29,16 +152,44 @@ impl Options {
             end,
             warmup: Duration::zero(),
             resolution: Resolution::Day,
+            clock_mode: ClockMode::Dense,
+            exchange: Exchange::Nyse,
+            extended_hours: false,
             normalize: false,
             outdir: None,
+            download_policy: DownloadPolicy::Fail,
+            validation: Validation::default(),
+            missing_bar_policy: MissingBarPolicy::ForwardFill,
+            prevent_look_ahead: true,
+            bar_timestamp: BarTimestamp::Open,
         }
     }
 
+    /// The first day of data needed, including the warmup before `start`.
+    pub fn data_start(&self) -> NaiveDate {
+        (self.start.and_hms_opt(0, 0, 0).unwrap() - self.warmup).date()
+    }
+
     pub fn set_resolution(mut self, resolution: Resolution) -> Self {
         self.resolution = resolution;
         self
     }
 
+    pub fn set_clock_mode(mut self, clock_mode: ClockMode) -> Self {
+        self.clock_mode = clock_mode;
+        self
+    }
+
+    pub fn set_exchange(mut self, exchange: Exchange) -> Self {
+        self.exchange = exchange;
+        self
+    }
+
+    pub fn set_extended_hours(mut self, extended_hours: bool) -> Self {
+        self.extended_hours = extended_hours;
+        self
+    }
+
     pub fn set_warmup(mut self, warmup: Duration) -> Self {
         self.warmup = warmup;
         self
@@ -49,6 +200,31 @@ impl Options {
         self
     }
 
+    pub fn set_download_policy(mut self, download_policy: DownloadPolicy) -> Self {
+        self.download_policy = download_policy;
+        self
+    }
+
+    pub fn set_validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    pub fn set_missing_bar_policy(mut self, missing_bar_policy: MissingBarPolicy) -> Self {
+        self.missing_bar_policy = missing_bar_policy;
+        self
+    }
+
+    pub fn set_prevent_look_ahead(mut self, prevent_look_ahead: bool) -> Self {
+        self.prevent_look_ahead = prevent_look_ahead;
+        self
+    }
+
+    pub fn set_bar_timestamp(mut self, bar_timestamp: BarTimestamp) -> Self {
+        self.bar_timestamp = bar_timestamp;
+        self
+    }
+
     pub fn set_outdir<T: ToString>(mut self, outdir: T) -> Self {
         self.outdir = Some(outdir.to_string());
         self
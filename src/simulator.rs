@@ -1,78 +1,152 @@
 use crate::brokerage::{
     actor::{BrokerageActor, Event},
-    handle::Brokerage,
-    order::{Order, OrderStatus},
+    fill::IntrabarPath,
+};
+use crate::data::{
+    error::Error, provider::DataProvider, report::DownloadReport, validation::ValidationReport,
+};
+use crate::finance::{
+    commission::{Commission, NoCommission},
+    slippage::{NoSlippage, Slippage},
+};
+use crate::markets::{
+    actor::MarketActor, clock::MarketState, data_manager::DataManager, handle::Market,
 };
-use crate::markets::{actor::MarketActor, clock::MarketState, handle::Market};
 use crate::statistics::Statistics;
 use crate::strategy::Strategy;
 use crate::Options;
-use chrono::DateTime;
-use chrono_tz::Tz;
 use rust_decimal::Decimal;
-use std::fs::{create_dir_all, remove_file, OpenOptions};
+use std::fs::{create_dir_all, OpenOptions};
 use std::io::Write;
+use std::sync::Arc;
 use tracing::{trace, Instrument};
 
 pub struct Simulator<S: Strategy + Send + Sync> {
-    brokerage: Brokerage,
+    cash: Decimal,
+    commission: Arc<dyn Commission>,
+    slippage: Arc<dyn Slippage>,
+    intrabar_path: IntrabarPath,
     market: Market,
     strategy: S,
     statistics: Statistics,
     data_options: Options,
+    download_report: DownloadReport,
+    validation_report: ValidationReport,
 }
 
 impl<S: Strategy + Send + Sync> Simulator<S> {
-    pub fn new(cash: Decimal, strategy: S, data_options: Options) -> Self {
-        let market = MarketActor::spawn(data_options.clone());
-        let brokerage = BrokerageActor::spawn(cash, market.clone());
+    /// Downloads and validates the data for the backtest and sets up the simulated market. The
+    /// brokerage is set up with the configured commission and slippage models when the backtest runs.
+    pub async fn new<D: DataProvider + 'static>(
+        cash: Decimal,
+        strategy: S,
+        data_options: Options,
+        data_provider: D,
+    ) -> Result<Self, Error> {
+        let mut data_manager = DataManager::new(data_options.clone(), Box::new(data_provider));
+        let download_report = data_manager.download_data().await?;
+        let validation_report = data_manager.validate()?;
+        let market = MarketActor::spawn(data_options.clone(), data_manager);
         let statistics = Statistics::new();
-        Self {
-            brokerage,
+        Ok(Self {
+            cash,
+            commission: Arc::new(NoCommission),
+            slippage: Arc::new(NoSlippage),
+            intrabar_path: IntrabarPath::default(),
             market,
             strategy,
             statistics,
             data_options,
-        }
+            download_report,
+            validation_report,
+        })
+    }
+
+    /// Charges `commission` on every fill instead of trading for free.
+    pub fn set_commission<C: Commission + 'static>(mut self, commission: C) -> Self {
+        self.commission = Arc::new(commission);
+        self
+    }
+
+    /// Fills orders away from the market price according to `slippage`.
+    pub fn set_slippage<T: Slippage + 'static>(mut self, slippage: T) -> Self {
+        self.slippage = Arc::new(slippage);
+        self
+    }
+
+    /// Sets the order in which limit and stop orders assume prices traded within a bar.
+    pub fn set_intrabar_path(mut self, intrabar_path: IntrabarPath) -> Self {
+        self.intrabar_path = intrabar_path;
+        self
     }
 
     pub async fn run(mut self) -> Result<(), S::Error> {
+        let brokerage = BrokerageActor::spawn(
+            self.cash,
+            self.market.clone(),
+            self.commission.clone(),
+            self.slippage.clone(),
+            self.intrabar_path,
+        );
         self.strategy.initialize().await;
-        let mut event_listener = self.brokerage.subscribe().await;
+        let mut event_listener = brokerage.subscribe().await;
         while !self.market.is_done().await {
             let (datetime, state) = futures::join!(self.market.datetime(), self.market.state());
             let span = tracing::debug_span!("Datetime", %datetime, ?state);
             async {
                 match state {
+                    MarketState::Warmup => {
+                        self.strategy
+                            .warmup(brokerage.clone(), self.market.clone())
+                            .instrument(tracing::trace_span!("Warmup"))
+                            .await
+                    }
                     MarketState::PreOpen => {
+                        brokerage.process_corporate_actions().await;
                         self.strategy
-                            .before_open(self.brokerage.clone(), self.market.clone())
+                            .before_open(brokerage.clone(), self.market.clone())
                             .instrument(tracing::trace_span!("Before open"))
                             .await
                     }
+                    MarketState::PreMarket => {
+                        brokerage.reconcile_active_orders().await;
+                        self.strategy
+                            .pre_market(brokerage.clone(), self.market.clone())
+                            .instrument(tracing::trace_span!("Pre-market"))
+                            .await
+                    }
                     MarketState::Opening => {
+                        brokerage.reconcile_active_orders().await;
                         self.strategy
-                            .at_open(self.brokerage.clone(), self.market.clone())
+                            .at_open(brokerage.clone(), self.market.clone())
                             .instrument(tracing::trace_span!("At open"))
                             .await
                     }
                     MarketState::Open => {
-                        self.brokerage.reconcile_active_orders().await;
+                        brokerage.reconcile_active_orders().await;
                         self.strategy
-                            .during_regular_hours(self.brokerage.clone(), self.market.clone())
+                            .during_regular_hours(brokerage.clone(), self.market.clone())
                             .instrument(tracing::trace_span!("Regular hours"))
                             .await
                     }
                     MarketState::Closing => {
+                        brokerage.reconcile_active_orders().await;
                         self.strategy
-                            .at_close(self.brokerage.clone(), self.market.clone())
+                            .at_close(brokerage.clone(), self.market.clone())
                             .instrument(tracing::trace_span!("At close"))
                             .await
                     }
+                    MarketState::AfterHours => {
+                        brokerage.reconcile_active_orders().await;
+                        self.strategy
+                            .after_hours(brokerage.clone(), self.market.clone())
+                            .instrument(tracing::trace_span!("After hours"))
+                            .await
+                    }
                     MarketState::Closed => {
-                        self.brokerage.expire_orders().await;
+                        brokerage.expire_orders().await;
                         self.strategy
-                            .after_close(self.brokerage.clone(), self.market.clone())
+                            .after_close(brokerage.clone(), self.market.clone())
                             .instrument(tracing::trace_span!("After close"))
                             .await?;
                         Ok(())
@@ -83,9 +157,12 @@ impl<S: Strategy + Send + Sync> Simulator<S> {
                     self.strategy.on_event(event.clone()).await?;
                     self.handle_event(event)
                 }
-                let equity = self.brokerage.get_equity().await;
-                trace!("Equity: {:.2}", equity);
-                self.statistics.record_equity(datetime, equity);
+                // The backtest only starts after the warmup
+                if state != MarketState::Warmup {
+                    let equity = brokerage.get_equity().await;
+                    trace!("Equity: {:.2}", equity);
+                    self.statistics.record_equity(datetime, equity);
+                }
                 self.market.tick().await;
                 Ok(())
             }
@@ -97,19 +174,7 @@ impl<S: Strategy + Send + Sync> Simulator<S> {
     }
 
     fn handle_event(&mut self, event: Event) {
-        self.statistics.record_event(event.clone());
-        match event {
-            Event::OrderUpdate {
-                status,
-                order,
-                time,
-            } => self.handle_order_update(status, order, time),
-            Event::Commission { amount } => self.statistics.increase_commission(amount),
-        }
-    }
-
-    fn handle_order_update(&mut self, status: OrderStatus, _order: Order, _time: DateTime<Tz>) {
-        self.statistics.handle_order(&status)
+        self.statistics.handle_event(event)
     }
 
     pub fn generate_report(self) {
@@ -119,34 +184,34 @@ impl<S: Strategy + Send + Sync> Simulator<S> {
             .unwrap_or_else(|| "out".to_string());
         let _ = create_dir_all(outdir.clone());
         let filename = format!("{}/statistics.txt", outdir);
-        let _ = remove_file(filename.clone());
         let mut file = OpenOptions::new()
-            .create(true)
             .write(true)
+            .create(true)
+            .truncate(true)
             .open(filename)
             .unwrap();
         write!(file, "{}", self.statistics).unwrap();
         file.flush().unwrap();
 
         let filename = format!("{}/equity.csv", outdir);
-        let _ = remove_file(filename.clone());
         let file = OpenOptions::new()
-            .create(true)
             .write(true)
+            .create(true)
+            .truncate(true)
             .open(filename)
             .unwrap();
         let mut wtr = csv::Writer::from_writer(file);
-        wtr.write_record(&["datetime", "equity"]).unwrap();
+        wtr.write_record(["datetime", "equity"]).unwrap();
         for (d, e) in self.statistics.equity {
             wtr.write_record(&[d.to_string(), e.to_string()]).unwrap()
         }
         wtr.flush().unwrap();
 
         let filename = format!("{}/event_log.json", outdir);
-        let _ = remove_file(filename.clone());
         let mut file = OpenOptions::new()
-            .create(true)
             .write(true)
+            .create(true)
+            .truncate(true)
             .open(filename)
             .unwrap();
         write!(
@@ -156,5 +221,39 @@ impl<S: Strategy + Send + Sync> Simulator<S> {
         )
         .unwrap();
         file.flush().unwrap();
+
+        if !self.download_report.is_empty() {
+            let filename = format!("{}/download_report.json", outdir);
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(filename)
+                .unwrap();
+            write!(
+                file,
+                "{}",
+                serde_json::to_string_pretty(&self.download_report).unwrap()
+            )
+            .unwrap();
+            file.flush().unwrap();
+        }
+
+        if !self.validation_report.is_empty() {
+            let filename = format!("{}/validation_report.json", outdir);
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(filename)
+                .unwrap();
+            write!(
+                file,
+                "{}",
+                serde_json::to_string_pretty(&self.validation_report).unwrap()
+            )
+            .unwrap();
+            file.flush().unwrap();
+        }
     }
 }
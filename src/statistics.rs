@@ -58,6 +58,7 @@ Expired:   {:>digits$}
 pub struct Statistics {
     order_counts: OrderCounts,
     commission_paid: Decimal,
+    slippage_paid: Decimal,
     pub equity: Vec<(DateTime<Tz>, Decimal)>,
     pub event_log: Vec<Event>,
 }
@@ -67,6 +68,7 @@ impl Statistics {
         Self {
             order_counts: OrderCounts::default(),
             commission_paid: Decimal::ZERO,
+            slippage_paid: Decimal::ZERO,
             equity: Vec::new(),
             event_log: Vec::new(),
         }
@@ -76,6 +78,17 @@ impl Statistics {
         self.event_log.push(event)
     }
 
+    /// Records `event` and updates the order counts and costs it affects.
+    pub fn handle_event(&mut self, event: Event) {
+        self.record_event(event.clone());
+        match event {
+            Event::OrderUpdate { status, .. } => self.handle_order(&status),
+            Event::Commission { amount } => self.increase_commission(amount),
+            Event::Slippage { amount } => self.increase_slippage(amount),
+            Event::Split { .. } | Event::Dividend { .. } => (),
+        }
+    }
+
     pub fn handle_order(&mut self, status: &OrderStatus) {
         match status {
             OrderStatus::Submitted => self.order_counts.submitted += 1,
@@ -83,7 +96,7 @@ impl Statistics {
             OrderStatus::Filled { .. } => self.order_counts.filled += 1,
             OrderStatus::Rejected => self.order_counts.rejected += 1,
             OrderStatus::Expired => self.order_counts.expired += 1,
-            OrderStatus::PartiallyFilled => (),
+            OrderStatus::Triggered { .. } | OrderStatus::PartiallyFilled { .. } => (),
         }
     }
 
@@ -95,6 +108,15 @@ impl Statistics {
         self.commission_paid += amount
     }
 
+    pub fn increase_slippage(&mut self, amount: Decimal) {
+        self.slippage_paid += amount
+    }
+
+    /// The total cost of filling orders away from the market price.
+    pub fn slippage_paid(&self) -> Decimal {
+        self.slippage_paid
+    }
+
     pub fn max_drawdown(&self) -> Decimal {
         #[derive(Default)]
         struct State {
@@ -132,6 +154,16 @@ Paid: {:>9}
              "#,
             self.commission_paid.round_dp(2)
         )?;
+        write!(
+            f,
+            r#"
+===============
+   Slippage
+===============
+Paid: {:>9}
+             "#,
+            self.slippage_paid.round_dp(2)
+        )?;
 
         write!(
             f,
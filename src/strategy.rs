@@ -9,6 +9,12 @@ pub trait Strategy {
 
     async fn initialize(&mut self) {}
 
+    /// Called on every tick of the regular sessions during the warmup before the start of the
+    /// backtest. Orders placed during the warmup are rejected.
+    async fn warmup(&mut self, brokerage: Brokerage, market: Market) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     async fn before_open(
         &mut self,
         brokerage: Brokerage,
@@ -21,6 +27,15 @@ pub trait Strategy {
         Ok(())
     }
 
+    /// Called on every tick of pre-market trading when extended hours are enabled.
+    async fn pre_market(
+        &mut self,
+        brokerage: Brokerage,
+        market: Market,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     async fn at_open(&mut self, brokerage: Brokerage, market: Market) -> Result<(), Self::Error> {
         Ok(())
     }
@@ -37,6 +52,15 @@ pub trait Strategy {
         Ok(())
     }
 
+    /// Called on every tick of after-hours trading when extended hours are enabled.
+    async fn after_hours(
+        &mut self,
+        brokerage: Brokerage,
+        market: Market,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     async fn after_close(
         &mut self,
         brokerage: Brokerage,
@@ -0,0 +1,41 @@
+use super::exchange_calendar::ExchangeCalendar;
+use super::nyse_calendar::NyseCalendar;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+
+lazy_static! {
+    static ref OPENING_TIME: NaiveTime = NaiveTime::from_hms_opt(17, 0, 0).unwrap();
+    static ref CLOSING_TIME: NaiveTime = NaiveTime::from_hms_opt(16, 0, 0).unwrap();
+    static ref EARLY_CLOSING_TIME: NaiveTime = NaiveTime::from_hms_opt(12, 15, 0).unwrap();
+}
+
+/// CME Globex hours for equity index futures. Each trading day's session opens at 17:00 Central
+/// Time on the previous day and closes at 16:00. Holidays and early closes follow the NYSE, which
+/// is a close approximation for the equity products.
+pub struct CmeCalendar;
+
+impl ExchangeCalendar for CmeCalendar {
+    fn timezone(&self) -> Tz {
+        Tz::America__Chicago
+    }
+
+    fn is_holiday(&self, date: NaiveDate) -> bool {
+        NyseCalendar.is_holiday(date)
+    }
+
+    fn is_early_close(&self, date: NaiveDate) -> bool {
+        NyseCalendar.is_early_close(date)
+    }
+
+    fn session(&self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        if !self.is_trading_day(date) {
+            return None;
+        }
+        let opening = date.pred_opt().unwrap().and_time(*OPENING_TIME);
+        if self.is_early_close(date) {
+            Some((opening, date.and_time(*EARLY_CLOSING_TIME)))
+        } else {
+            Some((opening, date.and_time(*CLOSING_TIME)))
+        }
+    }
+}
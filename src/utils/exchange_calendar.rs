@@ -0,0 +1,254 @@
+use super::cme_calendar::CmeCalendar;
+use super::lse_calendar::LseCalendar;
+use super::nyse_calendar::NyseCalendar;
+use super::tsx_calendar::TsxCalendar;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveDateTime, TimeZone, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// The trading days and session times of an exchange.
+///
+/// Session times are local to the exchange's timezone, and a session may start on the day before
+/// the trading day it belongs to, as is the case for futures that trade overnight.
+pub trait ExchangeCalendar: Send + Sync {
+    fn timezone(&self) -> Tz;
+
+    fn is_holiday(&self, date: NaiveDate) -> bool;
+
+    fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.is_holiday(date)
+    }
+
+    fn is_early_close(&self, _date: NaiveDate) -> bool {
+        false
+    }
+
+    /// The opening and closing time of the regular session on `date`, or `None` if the exchange
+    /// is closed.
+    fn session(&self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)>;
+
+    /// The opening and closing time of the session on `date` including pre-market and
+    /// after-hours trading. Exchanges without extended hours trade only the regular session.
+    fn extended_session(&self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        self.session(date)
+    }
+
+    fn next_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        let mut date = date.succ_opt().unwrap();
+        while !self.is_trading_day(date) {
+            date = date.succ_opt().unwrap()
+        }
+        date
+    }
+
+    fn previous_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        let mut date = date.pred_opt().unwrap();
+        while !self.is_trading_day(date) {
+            date = date.pred_opt().unwrap()
+        }
+        date
+    }
+
+    fn opening(&self, date: NaiveDate) -> Option<DateTime<Tz>> {
+        let (opening, _) = self.session(date)?;
+        self.timezone().from_local_datetime(&opening).earliest()
+    }
+
+    fn closing(&self, date: NaiveDate) -> Option<DateTime<Tz>> {
+        let (_, closing) = self.session(date)?;
+        self.timezone().from_local_datetime(&closing).earliest()
+    }
+
+    fn extended_opening(&self, date: NaiveDate) -> Option<DateTime<Tz>> {
+        let (opening, _) = self.extended_session(date)?;
+        self.timezone().from_local_datetime(&opening).earliest()
+    }
+
+    fn extended_closing(&self, date: NaiveDate) -> Option<DateTime<Tz>> {
+        let (_, closing) = self.extended_session(date)?;
+        self.timezone().from_local_datetime(&closing).earliest()
+    }
+
+    /// The trading day whose session contains `datetime`, if any.
+    fn trading_date(&self, datetime: DateTime<Tz>) -> Option<NaiveDate> {
+        let local = datetime.with_timezone(&self.timezone()).naive_local();
+        let date = local.date();
+        [date, date.succ_opt().unwrap(), date.pred_opt().unwrap()]
+            .iter()
+            .copied()
+            .find(|d| {
+                self.session(*d)
+                    .filter(|(opening, closing)| (*opening..*closing).contains(&local))
+                    .is_some()
+            })
+    }
+}
+
+/// A market that trades around the clock every day of the year, such as crypto currencies. Each
+/// trading day runs from midnight to midnight UTC.
+pub struct AlwaysOpenCalendar;
+
+impl ExchangeCalendar for AlwaysOpenCalendar {
+    fn timezone(&self) -> Tz {
+        Tz::UTC
+    }
+
+    fn is_holiday(&self, _date: NaiveDate) -> bool {
+        false
+    }
+
+    fn is_trading_day(&self, _date: NaiveDate) -> bool {
+        true
+    }
+
+    fn session(&self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        let opening = date.and_hms_opt(0, 0, 0).unwrap();
+        Some((opening, opening + Duration::days(1)))
+    }
+}
+
+/// A calendar for an exchange that isn't built in. Custom calendars are only equal to clones of
+/// themselves.
+#[derive(Clone)]
+pub struct CustomCalendar(pub Arc<dyn ExchangeCalendar>);
+
+impl CustomCalendar {
+    fn address(&self) -> *const () {
+        Arc::as_ptr(&self.0) as *const ()
+    }
+}
+
+impl fmt::Debug for CustomCalendar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CustomCalendar")
+            .field(&self.0.timezone())
+            .finish()
+    }
+}
+
+impl PartialEq for CustomCalendar {
+    fn eq(&self, other: &Self) -> bool {
+        self.address() == other.address()
+    }
+}
+
+impl Eq for CustomCalendar {}
+
+impl Hash for CustomCalendar {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address().hash(state)
+    }
+}
+
+/// The exchange whose calendar drives the simulation.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, Hash, PartialEq)]
+pub enum Exchange {
+    /// New York Stock Exchange
+    Nyse,
+    /// London Stock Exchange
+    Lse,
+    /// Toronto Stock Exchange
+    Tsx,
+    /// CME Globex equity index futures
+    Cme,
+    AlwaysOpen,
+    /// A calendar supplied by the user. It can't be serialized.
+    #[serde(skip)]
+    Custom(CustomCalendar),
+}
+
+impl Exchange {
+    pub fn custom<T: ExchangeCalendar + 'static>(calendar: T) -> Self {
+        Self::Custom(CustomCalendar(Arc::new(calendar)))
+    }
+
+    pub fn calendar(&self) -> Arc<dyn ExchangeCalendar> {
+        match self {
+            Self::Nyse => Arc::new(NyseCalendar),
+            Self::Lse => Arc::new(LseCalendar),
+            Self::Tsx => Arc::new(TsxCalendar),
+            Self::Cme => Arc::new(CmeCalendar),
+            Self::AlwaysOpen => Arc::new(AlwaysOpenCalendar),
+            Self::Custom(calendar) => calendar.0.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono_tz::US::Eastern;
+
+    #[test]
+    fn it_finds_the_session_of_a_datetime() {
+        let nyse = Exchange::Nyse.calendar();
+        let monday = NaiveDate::from_ymd_opt(2021, 1, 4).unwrap();
+        assert_eq!(
+            nyse.trading_date(Eastern.with_ymd_and_hms(2021, 1, 4, 10, 0, 0).unwrap()),
+            Some(monday)
+        );
+        assert_eq!(
+            nyse.trading_date(Eastern.with_ymd_and_hms(2021, 1, 4, 16, 0, 0).unwrap()),
+            None
+        );
+        assert_eq!(
+            nyse.next_trading_day(NaiveDate::from_ymd_opt(2021, 1, 1).unwrap()),
+            monday
+        );
+
+        // Globex opens the evening before the trading day
+        let cme = Exchange::Cme.calendar();
+        assert_eq!(
+            cme.trading_date(Eastern.with_ymd_and_hms(2021, 1, 3, 19, 0, 0).unwrap()),
+            Some(monday)
+        );
+        assert_eq!(
+            cme.trading_date(Eastern.with_ymd_and_hms(2021, 1, 4, 17, 30, 0).unwrap()),
+            None
+        );
+
+        let crypto = Exchange::AlwaysOpen.calendar();
+        assert!(crypto.is_trading_day(NaiveDate::from_ymd_opt(2021, 1, 2).unwrap()));
+        assert_eq!(
+            crypto.trading_date(Eastern.with_ymd_and_hms(2021, 1, 1, 20, 0, 0).unwrap()),
+            Some(NaiveDate::from_ymd_opt(2021, 1, 2).unwrap())
+        );
+    }
+
+    /// Trades NYSE hours on every weekday, holidays included.
+    struct WeekdayCalendar;
+
+    impl ExchangeCalendar for WeekdayCalendar {
+        fn timezone(&self) -> Tz {
+            chrono_tz::US::Eastern
+        }
+
+        fn is_holiday(&self, _date: NaiveDate) -> bool {
+            false
+        }
+
+        fn session(&self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+            if !self.is_trading_day(date) {
+                return None;
+            }
+            Some((
+                date.and_hms_opt(9, 30, 0).unwrap(),
+                date.and_hms_opt(16, 0, 0).unwrap(),
+            ))
+        }
+    }
+
+    #[test]
+    fn it_uses_custom_calendars() {
+        let exchange = Exchange::custom(WeekdayCalendar);
+        assert_eq!(exchange.clone(), exchange);
+        assert_ne!(exchange, Exchange::custom(WeekdayCalendar));
+        let new_years_day = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap();
+        assert!(exchange.calendar().is_trading_day(new_years_day));
+        assert!(!Exchange::Nyse.calendar().is_trading_day(new_years_day));
+        assert!(serde_json::to_string(&exchange).is_err());
+    }
+}
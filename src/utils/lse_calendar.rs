@@ -0,0 +1,168 @@
+use super::exchange_calendar::ExchangeCalendar;
+use super::nyse_calendar::find_weekday;
+use bdays::easter::easter_naive_date;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use chrono_tz::Tz;
+
+lazy_static! {
+    static ref OPENING_TIME: NaiveTime = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
+    static ref CLOSING_TIME: NaiveTime = NaiveTime::from_hms_opt(16, 30, 0).unwrap();
+    static ref EARLY_CLOSING_TIME: NaiveTime = NaiveTime::from_hms_opt(12, 30, 0).unwrap();
+}
+
+/// New Year's Day, moved to the following Monday when it falls on a weekend.
+pub(crate) fn new_years_day(yy: i32) -> NaiveDate {
+    let date = NaiveDate::from_ymd_opt(yy, 1, 1).unwrap();
+    match date.weekday() {
+        Weekday::Sat => date + Duration::days(2),
+        Weekday::Sun => date + Duration::days(1),
+        _ => date,
+    }
+}
+
+/// Christmas and Boxing Day, moved to the following weekdays when they fall on a weekend.
+pub(crate) fn christmas_holidays(yy: i32) -> [NaiveDate; 2] {
+    let christmas = NaiveDate::from_ymd_opt(yy, 12, 25).unwrap();
+    match christmas.weekday() {
+        Weekday::Fri => [christmas, NaiveDate::from_ymd_opt(yy, 12, 28).unwrap()],
+        Weekday::Sat => [
+            NaiveDate::from_ymd_opt(yy, 12, 27).unwrap(),
+            NaiveDate::from_ymd_opt(yy, 12, 28).unwrap(),
+        ],
+        Weekday::Sun => [
+            NaiveDate::from_ymd_opt(yy, 12, 26).unwrap(),
+            NaiveDate::from_ymd_opt(yy, 12, 27).unwrap(),
+        ],
+        _ => [christmas, NaiveDate::from_ymd_opt(yy, 12, 26).unwrap()],
+    }
+}
+
+pub struct LseCalendar;
+
+impl ExchangeCalendar for LseCalendar {
+    fn timezone(&self) -> Tz {
+        Tz::Europe__London
+    }
+
+    fn is_holiday(&self, date: NaiveDate) -> bool {
+        let yy = date.year();
+
+        // New Year's Day
+        if new_years_day(yy) == date {
+            return true;
+        }
+
+        // Good Friday and Easter Monday
+        let easter = easter_naive_date(yy).unwrap();
+        if (easter - Duration::days(2)) == date || (easter + Duration::days(1)) == date {
+            return true;
+        }
+
+        // Early May bank holiday, moved for VE day anniversaries
+        let early_may = match yy {
+            1995 | 2020 => NaiveDate::from_ymd_opt(yy, 5, 8).unwrap(),
+            _ => find_weekday(Weekday::Mon, yy, 5, 1, true),
+        };
+        if early_may == date {
+            return true;
+        }
+
+        // Spring bank holiday, moved for royal jubilees
+        let spring = match yy {
+            2002 => NaiveDate::from_ymd_opt(yy, 6, 4).unwrap(),
+            2012 => NaiveDate::from_ymd_opt(yy, 6, 4).unwrap(),
+            2022 => NaiveDate::from_ymd_opt(yy, 6, 2).unwrap(),
+            _ => find_weekday(Weekday::Mon, yy, 5, 1, false),
+        };
+        if spring == date {
+            return true;
+        }
+
+        // Summer bank holiday
+        if find_weekday(Weekday::Mon, yy, 8, 1, false) == date {
+            return true;
+        }
+
+        // Christmas and Boxing Day
+        if christmas_holidays(yy).contains(&date) {
+            return true;
+        }
+
+        // Special closures
+        let special_closures = [
+            // Coronation of King Charles III
+            NaiveDate::from_ymd_opt(2023, 5, 8).unwrap(),
+            // Queen Elizabeth II's funeral
+            NaiveDate::from_ymd_opt(2022, 9, 19).unwrap(),
+            // Platinum jubilee
+            NaiveDate::from_ymd_opt(2022, 6, 3).unwrap(),
+            // Diamond jubilee
+            NaiveDate::from_ymd_opt(2012, 6, 5).unwrap(),
+            // Royal wedding
+            NaiveDate::from_ymd_opt(2011, 4, 29).unwrap(),
+            // Golden jubilee
+            NaiveDate::from_ymd_opt(2002, 6, 3).unwrap(),
+            // Millennium
+            NaiveDate::from_ymd_opt(1999, 12, 31).unwrap(),
+        ];
+
+        special_closures.contains(&date)
+    }
+
+    /// Christmas Eve and New Year's Eve close early, at 12:30.
+    fn is_early_close(&self, date: NaiveDate) -> bool {
+        self.is_trading_day(date) && date.month() == 12 && (date.day() == 24 || date.day() == 31)
+    }
+
+    fn session(&self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        if !self.is_trading_day(date) {
+            None
+        } else if self.is_early_close(date) {
+            Some((
+                date.and_time(*OPENING_TIME),
+                date.and_time(*EARLY_CLOSING_TIME),
+            ))
+        } else {
+            Some((date.and_time(*OPENING_TIME), date.and_time(*CLOSING_TIME)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // https://www.londonstockexchange.com/equities-trading/business-days
+    use super::*;
+
+    #[test]
+    fn holidays() {
+        let holidays = [
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 4, 2).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 4, 5).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 5, 3).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 5, 31).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 8, 30).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 12, 27).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 12, 28).unwrap(),
+            NaiveDate::from_ymd_opt(2022, 1, 3).unwrap(),
+        ];
+        let mut date = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap();
+        while date < NaiveDate::from_ymd_opt(2022, 1, 31).unwrap() {
+            assert_eq!(
+                LseCalendar.is_holiday(date),
+                holidays.contains(&date),
+                "{}",
+                date
+            );
+            date += Duration::days(1)
+        }
+        let date = NaiveDate::from_ymd_opt(2021, 12, 24).unwrap();
+        assert_eq!(
+            LseCalendar.session(date),
+            Some((
+                date.and_hms_opt(8, 0, 0).unwrap(),
+                date.and_hms_opt(12, 30, 0).unwrap()
+            ))
+        );
+    }
+}
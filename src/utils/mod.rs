@@ -1,3 +1,7 @@
+pub mod cme_calendar;
+pub mod exchange_calendar;
+pub mod lse_calendar;
 pub mod nyse_calendar;
 pub mod progress;
 pub mod serde_tz;
+pub mod tsx_calendar;
@@ -1,5 +1,14 @@
-use bdays::{easter::easter_naive_date, HolidayCalendar};
-use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use super::exchange_calendar::ExchangeCalendar;
+use bdays::easter::easter_naive_date;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use chrono_tz::Tz;
+
+lazy_static! {
+    static ref OPENING_TIME: NaiveTime = NaiveTime::from_hms_opt(9, 30, 0).unwrap();
+    static ref CLOSING_TIME: NaiveTime = NaiveTime::from_hms_opt(16, 0, 0).unwrap();
+    static ref EARLY_CLOSING_TIME: NaiveTime = NaiveTime::from_hms_opt(13, 0, 0).unwrap();
+    static ref PRE_MARKET_OPENING_TIME: NaiveTime = NaiveTime::from_hms_opt(4, 0, 0).unwrap();
+}
 
 fn end_of_month(mut yy: i32, mut mm: u32) -> NaiveDate {
     assert!(mm <= 12);
@@ -11,11 +20,14 @@ fn end_of_month(mut yy: i32, mut mm: u32) -> NaiveDate {
         mm += 1;
     }
 
-    NaiveDate::from_ymd(yy, mm, 1).pred()
+    NaiveDate::from_ymd_opt(yy, mm, 1)
+        .unwrap()
+        .pred_opt()
+        .unwrap()
 }
 
 fn find_weekday_ascending(weekday: Weekday, yy: i32, mm: u32, occurrence: u32) -> NaiveDate {
-    let anchor = NaiveDate::from_ymd(yy, mm, 1);
+    let anchor = NaiveDate::from_ymd_opt(yy, mm, 1).unwrap();
     let mut offset = (weekday.number_from_monday() + 7 - anchor.weekday().number_from_monday()) % 7;
 
     if occurrence > 1 {
@@ -36,7 +48,13 @@ fn find_weekday_descending(weekday: Weekday, yy: i32, mm: u32, occurrence: u32)
     anchor - Duration::days(offset as i64)
 }
 
-fn find_weekday(weekday: Weekday, yy: i32, mm: u32, occurrence: u32, ascending: bool) -> NaiveDate {
+pub(crate) fn find_weekday(
+    weekday: Weekday,
+    yy: i32,
+    mm: u32,
+    occurrence: u32,
+    ascending: bool,
+) -> NaiveDate {
     if ascending {
         find_weekday_ascending(weekday, yy, mm, occurrence)
     } else {
@@ -46,7 +64,7 @@ fn find_weekday(weekday: Weekday, yy: i32, mm: u32, occurrence: u32, ascending:
 
 /// In the United States, if a holiday falls on Saturday, it's observed on the preceding Friday.
 /// If it falls on Sunday, it's observed on the next Monday.
-fn adjust_weekend_holidays_us(date: NaiveDate) -> NaiveDate {
+pub(crate) fn adjust_weekend_holidays_us(date: NaiveDate) -> NaiveDate {
     match date.weekday() {
         Weekday::Sat => date - Duration::days(1),
         Weekday::Sun => date + Duration::days(1),
@@ -56,13 +74,17 @@ fn adjust_weekend_holidays_us(date: NaiveDate) -> NaiveDate {
 
 pub struct NyseCalendar;
 
-impl<T: Datelike + Copy + PartialOrd> HolidayCalendar<T> for NyseCalendar {
-    fn is_holiday(&self, date: T) -> bool {
+impl ExchangeCalendar for NyseCalendar {
+    fn timezone(&self) -> Tz {
+        Tz::America__New_York
+    }
+
+    fn is_holiday(&self, date: NaiveDate) -> bool {
         let (yy, mm, dd) = (date.year(), date.month(), date.day());
-        let dt_naive = NaiveDate::from_ymd(yy, mm, dd);
+        let dt_naive = date;
 
         // New Year's Day
-        if adjust_weekend_holidays_us(NaiveDate::from_ymd(yy, 1, 1)) == dt_naive {
+        if adjust_weekend_holidays_us(NaiveDate::from_ymd_opt(yy, 1, 1).unwrap()) == dt_naive {
             return true;
         }
 
@@ -90,12 +112,14 @@ impl<T: Datelike + Copy + PartialOrd> HolidayCalendar<T> for NyseCalendar {
         }
 
         // Juneteenth
-        if yy >= 2022 && adjust_weekend_holidays_us(NaiveDate::from_ymd(yy, 6, 19)) == dt_naive {
+        if yy >= 2022
+            && adjust_weekend_holidays_us(NaiveDate::from_ymd_opt(yy, 6, 19).unwrap()) == dt_naive
+        {
             return true;
         }
 
         // Independence Day
-        if adjust_weekend_holidays_us(NaiveDate::from_ymd(yy, 7, 4)) == dt_naive {
+        if adjust_weekend_holidays_us(NaiveDate::from_ymd_opt(yy, 7, 4).unwrap()) == dt_naive {
             return true;
         }
 
@@ -110,7 +134,7 @@ impl<T: Datelike + Copy + PartialOrd> HolidayCalendar<T> for NyseCalendar {
         }
 
         // Christmas
-        if adjust_weekend_holidays_us(NaiveDate::from_ymd(yy, 12, 25)) == dt_naive {
+        if adjust_weekend_holidays_us(NaiveDate::from_ymd_opt(yy, 12, 25).unwrap()) == dt_naive {
             return true;
         }
 
@@ -125,78 +149,82 @@ impl<T: Datelike + Copy + PartialOrd> HolidayCalendar<T> for NyseCalendar {
 
         // Special closures
         let special_closures = [
+            // President Carter's funeral
+            NaiveDate::from_ymd_opt(2025, 1, 9).unwrap(),
             // George H.W. Bush's funeral
-            NaiveDate::from_ymd(2018, 12, 5),
+            NaiveDate::from_ymd_opt(2018, 12, 5).unwrap(),
             // Hurrican Sandy
-            NaiveDate::from_ymd(2012, 10, 29),
-            NaiveDate::from_ymd(2012, 10, 30),
+            NaiveDate::from_ymd_opt(2012, 10, 29).unwrap(),
+            NaiveDate::from_ymd_opt(2012, 10, 30).unwrap(),
+            // President Reagan's funeral
+            NaiveDate::from_ymd_opt(2004, 6, 11).unwrap(),
             // President Ford's funeral
-            NaiveDate::from_ymd(2007, 1, 2),
+            NaiveDate::from_ymd_opt(2007, 1, 2).unwrap(),
             // 9/11
-            NaiveDate::from_ymd(2001, 9, 11),
-            NaiveDate::from_ymd(2001, 9, 12),
-            NaiveDate::from_ymd(2001, 9, 13),
-            NaiveDate::from_ymd(2001, 9, 14),
+            NaiveDate::from_ymd_opt(2001, 9, 11).unwrap(),
+            NaiveDate::from_ymd_opt(2001, 9, 12).unwrap(),
+            NaiveDate::from_ymd_opt(2001, 9, 13).unwrap(),
+            NaiveDate::from_ymd_opt(2001, 9, 14).unwrap(),
             // President Nixon's funeral
-            NaiveDate::from_ymd(1994, 4, 27),
+            NaiveDate::from_ymd_opt(1994, 4, 27).unwrap(),
             // Hurrican Gloria
-            NaiveDate::from_ymd(1985, 9, 27),
+            NaiveDate::from_ymd_opt(1985, 9, 27).unwrap(),
             // 1977 Blackout
-            NaiveDate::from_ymd(1977, 7, 14),
+            NaiveDate::from_ymd_opt(1977, 7, 14).unwrap(),
             // President Johnson's funeral
-            NaiveDate::from_ymd(1973, 1, 25),
+            NaiveDate::from_ymd_opt(1973, 1, 25).unwrap(),
             // President Truman's funeral
-            NaiveDate::from_ymd(1972, 12, 25),
+            NaiveDate::from_ymd_opt(1972, 12, 25).unwrap(),
             // Moon landing
-            NaiveDate::from_ymd(1969, 7, 21),
+            NaiveDate::from_ymd_opt(1969, 7, 21).unwrap(),
             // President Eisenhower's funeral
-            NaiveDate::from_ymd(1969, 3, 31),
+            NaiveDate::from_ymd_opt(1969, 3, 31).unwrap(),
             // Heavy snow
-            NaiveDate::from_ymd(1969, 2, 10),
+            NaiveDate::from_ymd_opt(1969, 2, 10).unwrap(),
             // Day after Independence day
-            NaiveDate::from_ymd(1968, 7, 5),
+            NaiveDate::from_ymd_opt(1968, 7, 5).unwrap(),
             // Paperwork crisis
-            NaiveDate::from_ymd(1968, 6, 12),
-            NaiveDate::from_ymd(1968, 6, 19),
-            NaiveDate::from_ymd(1968, 6, 26),
-            NaiveDate::from_ymd(1968, 7, 3),
-            NaiveDate::from_ymd(1968, 7, 10),
-            NaiveDate::from_ymd(1968, 7, 17),
-            NaiveDate::from_ymd(1968, 7, 24),
-            NaiveDate::from_ymd(1968, 7, 31),
-            NaiveDate::from_ymd(1968, 8, 7),
-            NaiveDate::from_ymd(1968, 8, 14),
-            NaiveDate::from_ymd(1968, 8, 21),
-            NaiveDate::from_ymd(1968, 8, 28),
-            NaiveDate::from_ymd(1968, 9, 4),
-            NaiveDate::from_ymd(1968, 9, 11),
-            NaiveDate::from_ymd(1968, 9, 18),
-            NaiveDate::from_ymd(1968, 9, 25),
-            NaiveDate::from_ymd(1968, 10, 2),
-            NaiveDate::from_ymd(1968, 10, 9),
-            NaiveDate::from_ymd(1968, 10, 16),
-            NaiveDate::from_ymd(1968, 10, 23),
-            NaiveDate::from_ymd(1968, 10, 30),
-            NaiveDate::from_ymd(1968, 11, 6),
-            NaiveDate::from_ymd(1968, 11, 13),
-            NaiveDate::from_ymd(1968, 11, 20),
-            NaiveDate::from_ymd(1968, 11, 27),
-            NaiveDate::from_ymd(1968, 12, 4),
-            NaiveDate::from_ymd(1968, 12, 11),
-            NaiveDate::from_ymd(1968, 12, 18),
-            NaiveDate::from_ymd(1968, 12, 25),
+            NaiveDate::from_ymd_opt(1968, 6, 12).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 6, 19).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 6, 26).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 7, 3).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 7, 10).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 7, 17).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 7, 24).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 7, 31).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 8, 7).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 8, 14).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 8, 21).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 8, 28).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 9, 4).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 9, 11).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 9, 18).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 9, 25).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 10, 2).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 10, 9).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 10, 16).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 10, 23).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 10, 30).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 11, 6).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 11, 13).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 11, 20).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 11, 27).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 12, 4).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 12, 11).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 12, 18).unwrap(),
+            NaiveDate::from_ymd_opt(1968, 12, 25).unwrap(),
             // MLK assassination
-            NaiveDate::from_ymd(1968, 4, 9),
+            NaiveDate::from_ymd_opt(1968, 4, 9).unwrap(),
             // President Kennedy's funeral
-            NaiveDate::from_ymd(1963, 11, 25),
+            NaiveDate::from_ymd_opt(1963, 11, 25).unwrap(),
             // Day before Decoration day
-            NaiveDate::from_ymd(1961, 5, 29),
+            NaiveDate::from_ymd_opt(1961, 5, 29).unwrap(),
             // Day after Christmas
-            NaiveDate::from_ymd(1958, 12, 26),
+            NaiveDate::from_ymd_opt(1958, 12, 26).unwrap(),
             // Christmas eve
-            NaiveDate::from_ymd(1965, 12, 24),
-            NaiveDate::from_ymd(1956, 12, 24),
-            NaiveDate::from_ymd(1954, 12, 24),
+            NaiveDate::from_ymd_opt(1965, 12, 24).unwrap(),
+            NaiveDate::from_ymd_opt(1956, 12, 24).unwrap(),
+            NaiveDate::from_ymd_opt(1954, 12, 24).unwrap(),
         ];
 
         if special_closures.contains(&dt_naive) {
@@ -205,6 +233,41 @@ impl<T: Datelike + Copy + PartialOrd> HolidayCalendar<T> for NyseCalendar {
 
         false
     }
+
+    /// The day before Independence Day, the day after Thanksgiving and Christmas Eve close early,
+    /// at 13:00, as long as they are not holidays themselves.
+    fn is_early_close(&self, date: NaiveDate) -> bool {
+        if !self.is_trading_day(date) || date.year() < 1993 {
+            return false;
+        }
+        let (yy, mm, dd) = (date.year(), date.month(), date.day());
+        (mm == 7 && dd == 3)
+            || (find_weekday(Weekday::Thu, yy, 11, 4, true) + Duration::days(1)) == date
+            || (mm == 12 && dd == 24)
+    }
+
+    fn session(&self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        if !self.is_trading_day(date) {
+            None
+        } else if self.is_early_close(date) {
+            Some((
+                date.and_time(*OPENING_TIME),
+                date.and_time(*EARLY_CLOSING_TIME),
+            ))
+        } else {
+            Some((date.and_time(*OPENING_TIME), date.and_time(*CLOSING_TIME)))
+        }
+    }
+
+    /// Pre-market trading starts at 04:00 and after-hours trading runs for four hours after the
+    /// close, until 20:00 on regular days and 17:00 on early closes.
+    fn extended_session(&self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        let (_, closing) = self.session(date)?;
+        Some((
+            date.and_time(*PRE_MARKET_OPENING_TIME),
+            closing + Duration::hours(4),
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -216,50 +279,50 @@ mod test {
     lazy_static! {
      static ref HOLIDAYS: [NaiveDate; 28] = [
          // New Years
-         NaiveDate::from_ymd(2021, 1, 1),
-         NaiveDate::from_ymd(2023, 1, 2),
+         NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+         NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(),
          // MLK
-         NaiveDate::from_ymd(2021, 1, 18),
-         NaiveDate::from_ymd(2022, 1, 17),
-         NaiveDate::from_ymd(2023, 1, 16),
+         NaiveDate::from_ymd_opt(2021, 1, 18).unwrap(),
+         NaiveDate::from_ymd_opt(2022, 1, 17).unwrap(),
+         NaiveDate::from_ymd_opt(2023, 1, 16).unwrap(),
          // Presidents' day
-         NaiveDate::from_ymd(2021, 2, 15),
-         NaiveDate::from_ymd(2022, 2, 21),
-         NaiveDate::from_ymd(2023, 2, 20),
+         NaiveDate::from_ymd_opt(2021, 2, 15).unwrap(),
+         NaiveDate::from_ymd_opt(2022, 2, 21).unwrap(),
+         NaiveDate::from_ymd_opt(2023, 2, 20).unwrap(),
          // Good Friday
-         NaiveDate::from_ymd(2021, 4, 2),
-         NaiveDate::from_ymd(2022, 4, 15),
-         NaiveDate::from_ymd(2023, 4, 7),
+         NaiveDate::from_ymd_opt(2021, 4, 2).unwrap(),
+         NaiveDate::from_ymd_opt(2022, 4, 15).unwrap(),
+         NaiveDate::from_ymd_opt(2023, 4, 7).unwrap(),
          // Memorial Day
-         NaiveDate::from_ymd(2021, 5, 31),
-         NaiveDate::from_ymd(2022, 5, 30),
-         NaiveDate::from_ymd(2023, 5, 29),
+         NaiveDate::from_ymd_opt(2021, 5, 31).unwrap(),
+         NaiveDate::from_ymd_opt(2022, 5, 30).unwrap(),
+         NaiveDate::from_ymd_opt(2023, 5, 29).unwrap(),
          // Juneteenth
-         NaiveDate::from_ymd(2022, 6, 20),
-         NaiveDate::from_ymd(2023, 6, 19),
+         NaiveDate::from_ymd_opt(2022, 6, 20).unwrap(),
+         NaiveDate::from_ymd_opt(2023, 6, 19).unwrap(),
          // Independence Day
-         NaiveDate::from_ymd(2021, 7, 5),
-         NaiveDate::from_ymd(2022, 7, 4),
-         NaiveDate::from_ymd(2023, 7, 4),
+         NaiveDate::from_ymd_opt(2021, 7, 5).unwrap(),
+         NaiveDate::from_ymd_opt(2022, 7, 4).unwrap(),
+         NaiveDate::from_ymd_opt(2023, 7, 4).unwrap(),
          // Labor day
-         NaiveDate::from_ymd(2021, 9, 6),
-         NaiveDate::from_ymd(2022, 9, 5),
-         NaiveDate::from_ymd(2023, 9, 4),
+         NaiveDate::from_ymd_opt(2021, 9, 6).unwrap(),
+         NaiveDate::from_ymd_opt(2022, 9, 5).unwrap(),
+         NaiveDate::from_ymd_opt(2023, 9, 4).unwrap(),
          // Thanksgiving
-         NaiveDate::from_ymd(2021, 11, 25),
-         NaiveDate::from_ymd(2022, 11, 24),
-         NaiveDate::from_ymd(2023, 11, 23),
+         NaiveDate::from_ymd_opt(2021, 11, 25).unwrap(),
+         NaiveDate::from_ymd_opt(2022, 11, 24).unwrap(),
+         NaiveDate::from_ymd_opt(2023, 11, 23).unwrap(),
          // Christmas Day
-         NaiveDate::from_ymd(2021, 12, 24),
-         NaiveDate::from_ymd(2022, 12, 26),
-         NaiveDate::from_ymd(2023, 12, 25),
+         NaiveDate::from_ymd_opt(2021, 12, 24).unwrap(),
+         NaiveDate::from_ymd_opt(2022, 12, 26).unwrap(),
+         NaiveDate::from_ymd_opt(2023, 12, 25).unwrap(),
      ];
     }
 
     #[test]
     fn holidays() {
-        let mut date = NaiveDate::from_ymd(2021, 1, 1);
-        while date < NaiveDate::from_ymd(2024, 1, 1) {
+        let mut date = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap();
+        while date < NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() {
             if HOLIDAYS.contains(&date) {
                 assert!(CAL.is_holiday(date), "{} is not a holiday", date)
             } else {
@@ -268,4 +331,59 @@ mod test {
             date += Duration::days(1)
         }
     }
+
+    #[test]
+    fn early_closes() {
+        let early_closes = [
+            NaiveDate::from_ymd_opt(2019, 7, 3).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 11, 26).unwrap(),
+            NaiveDate::from_ymd_opt(2020, 12, 24).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 7, 3).unwrap(),
+        ];
+        for date in early_closes.iter() {
+            assert!(CAL.is_early_close(*date), "{}", date);
+            assert_eq!(
+                CAL.session(*date),
+                Some((
+                    date.and_hms_opt(9, 30, 0).unwrap(),
+                    date.and_hms_opt(13, 0, 0).unwrap()
+                ))
+            );
+            assert_eq!(
+                CAL.extended_session(*date),
+                Some((
+                    date.and_hms_opt(4, 0, 0).unwrap(),
+                    date.and_hms_opt(17, 0, 0).unwrap()
+                ))
+            );
+        }
+        // Independence Day observed on the 3rd, and Christmas observed on the 24th
+        assert_eq!(
+            CAL.session(NaiveDate::from_ymd_opt(2020, 7, 3).unwrap()),
+            None
+        );
+        assert_eq!(
+            CAL.session(NaiveDate::from_ymd_opt(2021, 12, 24).unwrap()),
+            None
+        );
+        let date = NaiveDate::from_ymd_opt(2021, 12, 23).unwrap();
+        assert_eq!(
+            CAL.session(date),
+            Some((
+                date.and_hms_opt(9, 30, 0).unwrap(),
+                date.and_hms_opt(16, 0, 0).unwrap()
+            ))
+        );
+        assert_eq!(
+            CAL.extended_session(date),
+            Some((
+                date.and_hms_opt(4, 0, 0).unwrap(),
+                date.and_hms_opt(20, 0, 0).unwrap()
+            ))
+        );
+        assert_eq!(
+            CAL.session(NaiveDate::from_ymd_opt(2012, 10, 29).unwrap()),
+            None
+        );
+    }
 }
@@ -0,0 +1,125 @@
+use super::exchange_calendar::ExchangeCalendar;
+use super::lse_calendar::{christmas_holidays, new_years_day};
+use super::nyse_calendar::find_weekday;
+use bdays::easter::easter_naive_date;
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use chrono_tz::Tz;
+
+lazy_static! {
+    static ref OPENING_TIME: NaiveTime = NaiveTime::from_hms_opt(9, 30, 0).unwrap();
+    static ref CLOSING_TIME: NaiveTime = NaiveTime::from_hms_opt(16, 0, 0).unwrap();
+    static ref EARLY_CLOSING_TIME: NaiveTime = NaiveTime::from_hms_opt(13, 0, 0).unwrap();
+}
+
+pub struct TsxCalendar;
+
+impl ExchangeCalendar for TsxCalendar {
+    fn timezone(&self) -> Tz {
+        Tz::America__Toronto
+    }
+
+    fn is_holiday(&self, date: NaiveDate) -> bool {
+        let yy = date.year();
+
+        // New Year's Day
+        if new_years_day(yy) == date {
+            return true;
+        }
+
+        // Family Day
+        if yy >= 2008 && find_weekday(Weekday::Mon, yy, 2, 3, true) == date {
+            return true;
+        }
+
+        // Good Friday
+        let easter = easter_naive_date(yy).unwrap();
+        if (easter - Duration::days(2)) == date {
+            return true;
+        }
+
+        // Victoria Day, the Monday before May 25th
+        let may_24 = NaiveDate::from_ymd_opt(yy, 5, 24).unwrap();
+        if (may_24 - Duration::days(may_24.weekday().num_days_from_monday() as i64)) == date {
+            return true;
+        }
+
+        // Canada Day
+        let canada_day = NaiveDate::from_ymd_opt(yy, 7, 1).unwrap();
+        let canada_day = match canada_day.weekday() {
+            Weekday::Sat => canada_day + Duration::days(2),
+            Weekday::Sun => canada_day + Duration::days(1),
+            _ => canada_day,
+        };
+        if canada_day == date {
+            return true;
+        }
+
+        // Civic Holiday
+        if find_weekday(Weekday::Mon, yy, 8, 1, true) == date {
+            return true;
+        }
+
+        // Labour Day
+        if find_weekday(Weekday::Mon, yy, 9, 1, true) == date {
+            return true;
+        }
+
+        // Thanksgiving
+        if find_weekday(Weekday::Mon, yy, 10, 2, true) == date {
+            return true;
+        }
+
+        // Christmas and Boxing Day
+        christmas_holidays(yy).contains(&date)
+    }
+
+    /// Christmas Eve closes early, at 13:00.
+    fn is_early_close(&self, date: NaiveDate) -> bool {
+        self.is_trading_day(date) && date.month() == 12 && date.day() == 24
+    }
+
+    fn session(&self, date: NaiveDate) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        if !self.is_trading_day(date) {
+            None
+        } else if self.is_early_close(date) {
+            Some((
+                date.and_time(*OPENING_TIME),
+                date.and_time(*EARLY_CLOSING_TIME),
+            ))
+        } else {
+            Some((date.and_time(*OPENING_TIME), date.and_time(*CLOSING_TIME)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    // https://www.tsx.com/trading/calendars-and-trading-hours/calendar
+    use super::*;
+
+    #[test]
+    fn holidays() {
+        let holidays = [
+            NaiveDate::from_ymd_opt(2021, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 2, 15).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 4, 2).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 5, 24).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 7, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 8, 2).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 9, 6).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 10, 11).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 12, 27).unwrap(),
+            NaiveDate::from_ymd_opt(2021, 12, 28).unwrap(),
+        ];
+        let mut date = NaiveDate::from_ymd_opt(2021, 1, 1).unwrap();
+        while date < NaiveDate::from_ymd_opt(2022, 1, 1).unwrap() {
+            assert_eq!(
+                TsxCalendar.is_holiday(date),
+                holidays.contains(&date),
+                "{}",
+                date
+            );
+            date += Duration::days(1)
+        }
+    }
+}